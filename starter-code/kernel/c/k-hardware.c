@@ -129,7 +129,7 @@ void segments_init(void) {
     // System calls get special handling.
     // Note that the last argument is '3'.  This means that unprivileged
     // (level-3) applications may generate these interrupts.
-    for (unsigned i = INT_SYS; i < INT_SYS + 16; ++i) {
+    for (unsigned i = INT_SYS; i < INT_SYS + 32; ++i) {
         set_gate(&interrupt_descriptors[i], X86GATE_INTERRUPT, 3,
                  (uint64_t) sys_int_handlers[i - INT_SYS]);
     }
@@ -569,12 +569,16 @@ int error_printf(int cpos, int color, const char* format, ...) {
 // check_keyboard
 //    Check for the user typing a control key. 'a', 'f', and 'e' cause a soft
 //    reboot where the kernel runs the allocator programs, "fork", or
-//    "forkexit", respectively. Control-C or 'q' exit the virtual machine.
+//    "forkexit", respectively. 'p' runs the allocators with the priority
+//    scheduler, the first one at the highest priority, and 'm' runs them
+//    with the multi-level feedback queue scheduler. 's' runs "test" with
+//    the stride scheduler. Control-C or 'q' exit the virtual machine.
 //    Returns key typed or -1 for no key.
 
 int check_keyboard(void) {
     int c = keyboard_readc();
-    if (c == 'a' || c == 'f' || c == 'e' || c == 't' || c =='2') {
+    if (c == 'a' || c == 'f' || c == 'e' || c == 't' || c =='2'
+        || c == 'p' || c == 'm' || c == 's') {
         // Install a temporary page table to carry us through the
         // process of reinitializing memory. This replicates work the
         // bootloader does.
@@ -601,6 +605,15 @@ int check_keyboard(void) {
         else if(c == '2'){
             argument = "test2";
         }
+        else if (c == 'p') {
+            argument = "sched=prio nice";
+        }
+        else if (c == 'm') {
+            argument = "sched=mlfq allocator";
+        }
+        else if (c == 's') {
+            argument = "sched=stride test";
+        }
         uintptr_t argument_ptr = (uintptr_t) argument;
         assert(argument_ptr < 0x100000000L);
         multiboot_info[4] = (uint32_t) argument_ptr;
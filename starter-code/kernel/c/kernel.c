@@ -28,9 +28,6 @@ proc* current;                  // pointer to currently executing proc
 #define HZ 100                  // timer interrupt frequency (interrupts/sec)
 unsigned ticks;                 // # timer interrupts so far
 
-void schedule(void);
-void run(proc* p) __attribute__((noreturn));
-
 uint8_t disp_global = 1;        // global flag to display memviewer
 
 // PAGEINFO
@@ -74,44 +71,6 @@ void memshow_physical(void);
 void memshow_virtual(x86_64_pagetable* pagetable, const char* name);
 void memshow_virtual_animate(void);
 
-// assign_physical_page(addr, owner)
-//    Allocates the page with physical address `addr` to the given owner.
-//    Fails if physical page `addr` was already allocated. Returns 0 on
-//    success and -1 on failure. Used by the program loader.
-
-int assign_physical_page(uintptr_t addr, int8_t owner) {
-    if ((addr & 0xFFF) != 0
-        || addr >= MEMSIZE_PHYSICAL
-        || pageinfo[PAGENUMBER(addr)].refcount != 0) {
-        return -1;
-    } else {
-        pageinfo[PAGENUMBER(addr)].refcount = 1;
-        pageinfo[PAGENUMBER(addr)].owner = owner;
-        return 0;
-    }
-}
-
-void syscall_mapping(proc* p){
-
-    uintptr_t mapping_ptr = p->p_registers.reg_rdi;
-    uintptr_t ptr = p->p_registers.reg_rsi;
-
-    //convert to physical address so kernel can write to it
-    vamapping map = virtual_memory_lookup(p->p_pagetable, mapping_ptr);
-
-    // check for write access
-    if((map.perm & (PTE_W|PTE_U)) != (PTE_W|PTE_U))
-        return;
-    uintptr_t endaddr = mapping_ptr + sizeof(vamapping) - 1;
-    // check for write access for end address
-    vamapping end_map = virtual_memory_lookup(p->p_pagetable, endaddr);
-    if((end_map.perm & (PTE_W|PTE_P)) != (PTE_W|PTE_P))
-        return;
-    // find the actual mapping now
-    vamapping ptr_lookup = virtual_memory_lookup(p->p_pagetable, ptr);
-    memcpy((void *)map.pa, &ptr_lookup, sizeof(vamapping));
-}
-
 void syscall_mem_tog(proc* process){
 
     pid_t p = process->p_registers.reg_rdi;
@@ -126,44 +85,6 @@ void syscall_mem_tog(proc* process){
 }
 
 
-// schedule
-//    Pick the next process to run and then run it.
-//    If there are no runnable processes, spins forever.
-
-void schedule(void) {
-    pid_t pid = current->p_pid;
-    while (1) {
-        pid = (pid + 1) % NPROC;
-        if (processes[pid].p_state == P_RUNNABLE) {
-            run(&processes[pid]);
-        }
-        // If Control-C was typed, exit the virtual machine.
-        check_keyboard();
-    }
-}
-
-
-// run(p)
-//    Run process `p`. This means reloading all the registers from
-//    `p->p_registers` using the `popal`, `popl`, and `iret` instructions.
-//
-//    As a side effect, sets `current = p`.
-
-void run(proc* p) {
-    assert(p->p_state == P_RUNNABLE);
-    current = p;
-
-    // Load the process's current pagetable.
-    set_pagetable(p->p_pagetable);
-
-    // This function is defined in k-exception.S. It restores the process's
-    // registers then jumps back to user mode.
-    exception_return(&p->p_registers);
-
- spinloop: goto spinloop;       // should never get here
-}
-
-
 // pageinfo_init
 //    Initialize the `pageinfo[]` array.
 
@@ -245,7 +166,8 @@ static void check_page_table_ownership_level(x86_64_pagetable* pt, int level,
     assert(pageinfo[PAGENUMBER(pt)].refcount == refcount);
     if (level < 3) {
         for (int index = 0; index < NPAGETABLEENTRIES; ++index) {
-            if (pt->entry[index]) {
+            // large pages (PTE_PS) map memory, not a lower page table
+            if (pt->entry[index] && !(pt->entry[index] & PTE_PS)) {
                 x86_64_pagetable* nextpt =
                     (x86_64_pagetable*) PTE_ADDR(pt->entry[index]);
                 check_page_table_ownership_level(nextpt, level + 1, owner, 1);
@@ -25,6 +25,15 @@ typedef struct proc {
     procstate_t p_state;                // process state (see above)
     x86_64_pagetable* p_pagetable;      // process's page table
     uint8_t display_status;             // process's display status for memviewer
+    uintptr_t p_heap_start;             // initial program break (end of loaded data)
+    uintptr_t p_brk;                    // current program break
+    uintptr_t p_stack_bottom;           // lowest mapped address of the stack
+    uintptr_t p_stack_limit;            // lowest address the stack may grow to
+    int p_priority;                     // scheduling priority (PRIO_MIN..PRIO_MAX)
+    pid_t p_ppid;                       // parent's pid, or 0
+    unsigned p_ticks;                   // timer interrupts while it was running
+    unsigned p_start_ticks;             // `ticks` when it started or was forked
+    unsigned p_tickets;                 // share of the CPU (1..TICKETS_MAX)
 } proc;
 
 #define NPROC 16                // maximum number of processes
@@ -80,12 +89,15 @@ extern x86_64_pagetable* kernel_pagetable;
 //    `PTE_W` (the memory is Writable), and `PTE_U` (the memory may be
 //    accessed by User applications). If `!(perm & PTE_P)`, `pa` is ignored.
 //
-//    Sometimes mapping memory will require allocating new page tables. The
-//    `allocator` function should return a newly allocated page, or NULL
-//    on allocation failure.
+//    Adding `PTE_PS` maps 2 MB-aligned parts of a kernel-only range (no
+//    `PTE_U`) with 2 MB pages. A 2 MB page is split into 4 KB pages when
+//    part of it is mapped again.
+//
+//    Sometimes mapping memory will require allocating new page tables;
+//    they are taken from the frame allocator and owned like `pagetable`.
 //
 //    Returns 0 if the map succeeds, -1 if it fails because a required
-//    page table was not allocated.
+//    page table could not be allocated.
 int virtual_memory_map(x86_64_pagetable* pagetable, uintptr_t va,
                        uintptr_t pa, size_t sz, int perm);
 
@@ -99,7 +111,7 @@ vamapping virtual_memory_lookup(x86_64_pagetable* pagetable, uintptr_t va);
 // assign_physical_page(addr, owner)
 //    Assigns the page with physical address `addr` to the given owner.
 //    Fails if physical page `addr` was already allocated. Returns 0 on
-//    success and -1 on failure. Used by the program loader.
+//    success and -1 on failure. Implemented by the Rust frame allocator.
 int assign_physical_page(uintptr_t addr, int8_t owner);
 
 // physical_memory_isreserved(pa)
@@ -173,16 +185,6 @@ void process_init(proc* p, int flags);
 #define PROCINIT_DISABLE_INTERRUPTS     0x02
 
 
-// program_load(p, programnumber)
-//    Load the code corresponding to program `programnumber` into the process
-//    `p` and set `p->p_registers.reg_eip` to its entry point. Calls
-//    `assign_physical_page` as required. Returns 0 on success and
-//    -1 on failure (e.g. out-of-memory). `allocator` is passed to
-//    `virtual_memory_map`.
-int program_load(proc* p, int programnumber,
-                 x86_64_pagetable* (*allocator)(void));
-
-
 // log_printf, log_vprintf
 //    Print debugging messages to the host's `log.txt` file. We run QEMU
 //    so that messages written to the QEMU "parallel port" end up in `log.txt`.
@@ -15,7 +15,7 @@ extern void timer_int_handler(void);
 //    Initialize the virtual memory system, including an initial page table
 //    `kernel_pagetable`.
 
-static x86_64_pagetable kernel_pagetables[5];
+static x86_64_pagetable kernel_pagetables[3];
 x86_64_pagetable *kernel_pagetable;
 
 void virtual_memory_init(void)
@@ -23,19 +23,17 @@ void virtual_memory_init(void)
     kernel_pagetable = &kernel_pagetables[0];
     memset(kernel_pagetables, 0, sizeof(kernel_pagetables));
 
-    // connect the pagetable pages
+    // connect the pagetable pages; physical memory is mapped with
+    // 2 MB pages directly from the L2 page table, so no L1 page
+    // tables are needed (MEMSIZE_PHYSICAL is a multiple of 2 MB)
     kernel_pagetables[0].entry[0] =
         (x86_64_pageentry_t)&kernel_pagetables[1] | PTE_P | PTE_W | PTE_U;
     kernel_pagetables[1].entry[0] =
         (x86_64_pageentry_t)&kernel_pagetables[2] | PTE_P | PTE_W | PTE_U;
-    kernel_pagetables[2].entry[0] =
-        (x86_64_pageentry_t)&kernel_pagetables[3] | PTE_P | PTE_W | PTE_U;
-    kernel_pagetables[2].entry[1] =
-        (x86_64_pageentry_t)&kernel_pagetables[4] | PTE_P | PTE_W | PTE_U;
 
-    // identity map the page table
+    // identity map the page table; kernel-only, so large pages can be used
     virtual_memory_map(kernel_pagetable, (uintptr_t)0, (uintptr_t)0,
-                       MEMSIZE_PHYSICAL, PTE_P | PTE_W | PTE_U);
+                       MEMSIZE_PHYSICAL, PTE_P | PTE_W | PTE_PS);
 
     // check if kernel is identity mapped
     for (uintptr_t addr = 0; addr < MEMSIZE_PHYSICAL; addr += PAGESIZE)
@@ -83,22 +81,6 @@ extern int virtual_memory_map(x86_64_pagetable *pagetable, uintptr_t va,
 // virtual_memory_lookup(pagetable, va)
 //    Returns information about the mapping of the virtual address `va` in
 //    `pagetable`. The information is returned as a `vamapping` object.
-
-vamapping virtual_memory_lookup(x86_64_pagetable *pagetable, uintptr_t va)
-{
-    x86_64_pagetable *pt = pagetable;
-    x86_64_pageentry_t pe = PTE_W | PTE_U | PTE_P;
-    for (int i = 0; i <= 3 && (pe & PTE_P); ++i)
-    {
-        pe = pt->entry[PAGEINDEX(va, i)] & ~(pe & (PTE_W | PTE_U));
-        pt = (x86_64_pagetable *)PTE_ADDR(pe);
-    }
-    vamapping vam = {-1, (uintptr_t)-1, 0};
-    if (pe & PTE_P)
-    {
-        vam.pn = PAGENUMBER(pe);
-        vam.pa = PTE_ADDR(pe) + PAGEOFFSET(va);
-        vam.perm = PTE_FLAGS(pe);
-    }
-    return vam;
-}
+//    Implemented in Rust (vm/vm.rs), including large pages.
+extern vamapping virtual_memory_lookup(x86_64_pagetable *pagetable,
+                                       uintptr_t va);
@@ -1,7 +1,7 @@
-// In this operating system, we lack the ability to dynamically allocate 
-// memory for features like `String` or the `format!` macro. You will implement
-// this feature in your final ptoject. For now, all string-based operations use 
-// statically allocated fixed-size buffers along with its custom traits.
+// The kernel heap (see heap/heap.rs) provides `alloc::string::String` and
+// `alloc::format!`, but the debugging macros keep using statically allocated
+// fixed-size buffers along with its custom traits: they must work before the
+// heap is initialized and when it has run out of memory.
 
 pub mod traits;
 
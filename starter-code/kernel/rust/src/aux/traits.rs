@@ -137,6 +137,13 @@ impl ToText for &str {
     }
 }
 
+// Implementation for heap-allocated strings.
+impl ToText for alloc::string::String {
+    fn to_text(&self, buf: &mut [u8]) -> usize {
+        self.as_str().to_text(buf)
+    }
+}
+
 // Implementation for unsigned 8-bit integers.
 impl ToText for u8 {
     fn to_text(&self, buf: &mut [u8]) -> usize {
@@ -33,6 +33,16 @@ pub struct ElfHeader {
 
 pub const ELF_MAGIC: u32 = 1179403647;
 
+// Values for elf_header::e_elf (the bytes after the magic number)
+pub const ELF_CLASS_64: u8 = 2;     // e_elf[0]: 64-bit objects
+pub const ELF_DATA_LSB: u8 = 1;     // e_elf[1]: little-endian data
+
+// Values for elf_header::e_type
+pub const ELF_ETYPE_EXEC: u16 = 2;
+
+// Values for elf_header::e_machine
+pub const ELF_MACHINE_X86_64: u16 = 62;
+
 // Values for elf_program::p_type
 pub const ELF_PTYPE_LOAD: u32 = 1;
 
@@ -20,6 +20,7 @@ pub type ProcstateT = c_uint;
 pub type PidT = c_int;
 
 pub const PTE_FLAGS_MASK: u64 = 0xFFF;
+pub const PTE_ADDR_MASK: u64 = 0x000F_FFFF_FFFF_F000;
 pub const PAGE_OFF_MASK: u64 = PAGESIZE - 1;
 
 // Paged memory constants
@@ -72,7 +73,7 @@ pub fn page_offset(addr: u64) -> u64 {
 // The physical address contained in a page table entry
 #[inline]
 pub fn pte_addr(pageentry: X86_64PageentryT) -> X86_64PageentryT {
-    pageentry & !0xFFF
+    pageentry & PTE_ADDR_MASK
 }
 
 // Page table entry flags
@@ -90,6 +91,7 @@ pub const PTE_U: X86_64PageentryT = 4;      // entry is User-accessible
 pub const PTE_A: X86_64PageentryT = 32;     // entry was Accessed (read/written)
 pub const PTE_D: X86_64PageentryT = 64;     // entry was Dirtied (written)
 pub const PTE_PS: X86_64PageentryT = 128;   // entry has a large Page Size
+pub const PTE_NX: X86_64PageentryT = 1 << 63; // entry is Not eXecutable (needs IA32_EFER_NXE)
 // - There are other flags too!
 
 // Page fault error flags
@@ -97,6 +99,7 @@ pub const PTE_PS: X86_64PageentryT = 128;   // entry has a large Page Size
 pub const PFERR_PRESENT: u8 = 0x1;   // Fault happened due to a protection violation (rather than due to a missing page)
 pub const PFERR_WRITE: u8 = 0x2;     // Fault happened on a write
 pub const PFERR_USER: u8 = 0x4;      // Fault happened in an application (user mode) (rather than kernel)
+pub const PFERR_INSTRUCTION: u8 = 0x10; // Fault happened on an instruction fetch
 
 #[repr(C)]
 #[repr(align(4096))]
@@ -188,6 +191,15 @@ pub struct Proc {
     pub p_state: ProcstateT,
     pub p_pagetable: *mut x86_64_pagetable,
     pub display_status: u8,
+    pub p_heap_start: u64,
+    pub p_brk: u64,
+    pub p_stack_bottom: u64,
+    pub p_stack_limit: u64,
+    pub p_priority: i32,
+    pub p_ppid: PidT,
+    pub p_ticks: u32,
+    pub p_start_ticks: u32,
+    pub p_tickets: u32,
 }
 
 unsafe impl Send for Proc {}
@@ -201,6 +213,15 @@ impl Default for Proc {
             p_state: P_FREE,
             p_pagetable: core::ptr::null_mut(),
             display_status: 0,
+            p_heap_start: 0,
+            p_brk: 0,
+            p_stack_bottom: 0,
+            p_stack_limit: 0,
+            p_priority: PRIO_DEFAULT,
+            p_ppid: 0,
+            p_ticks: 0,
+            p_start_ticks: 0,
+            p_tickets: TICKETS_DEFAULT,
         }
     }
 }
@@ -260,6 +281,33 @@ pub const INT_SYS_MAPPING: u32 = 54;
 pub const INT_SYS_MEM_TOG: u32 = 56;
 pub const INT_SYS_BRK: u32 = 57;
 pub const INT_SYS_SBRK: u32 = 58;
+pub const INT_SYS_MMAP: u32 = 59;
+pub const INT_SYS_MUNMAP: u32 = 60;
+pub const INT_SYS_MPROTECT: u32 = 61;
+pub const INT_SYS_SHM_CREATE: u32 = 62;
+pub const INT_SYS_SHM_ATTACH: u32 = 63;
+pub const INT_SYS_SHM_DETACH: u32 = 64;
+pub const INT_SYS_EXEC: u32 = 65;
+pub const INT_SYS_GETPRIORITY: u32 = 66;
+pub const INT_SYS_SETPRIORITY: u32 = 67;
+pub const INT_SYS_TICKS: u32 = 68;
+pub const INT_SYS_SET_TICKETS: u32 = 69;
+pub const INT_SYS_CPU_SHARE: u32 = 70;
+pub const INT_SYS_SLEEP: u32 = 71;
+
+// Memory protection for sys_mmap and sys_mprotect
+pub const PROT_NONE: i32 = 0;
+pub const PROT_READ: i32 = 1;
+pub const PROT_WRITE: i32 = 2;
+
+// Scheduling priorities for sys_getpriority and sys_setpriority
+pub const PRIO_MIN: i32 = 0;
+pub const PRIO_DEFAULT: i32 = 20;
+pub const PRIO_MAX: i32 = 39;
+
+// Tickets for sys_set_tickets
+pub const TICKETS_DEFAULT: u32 = 100;
+pub const TICKETS_MAX: u32 = 1000;
 
 
 /// Set the CR3 register (page table base register).
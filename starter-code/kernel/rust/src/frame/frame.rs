@@ -0,0 +1,278 @@
+// frame.rs
+//
+//    Physical frame allocator.
+
+use crate::frame::*;
+use crate::kernel::kernel::PageOwner;
+
+// FRAME ALLOCATOR
+//
+//    `pageinfo[]` stays the record of who owns each physical page and how
+//    many mappings refer to it; the C memory viewer and invariant checks read
+//    it directly. The frame allocator keeps a bitmap of the free pages next
+//    to it, one bit per page, so finding a free page never needs a walk over
+//    `pageinfo[]`: allocation looks at `NPAGES / 64` words and picks the
+//    lowest set bit of the first non-empty one.
+//
+//    All changes to `pageinfo[]` refcounts that free or allocate a page must
+//    go through `FRAMES` so the bitmap stays in sync.
+//
+//    A user page that fork shares is mapped by its owner and by other
+//    processes. `FRAMES` remembers those other sharers of each page, one bit
+//    per pid, so that when the owner lets go of a page that is still mapped
+//    the page can be handed to a remaining sharer without looking through
+//    every address space.
+
+const NWORDS: usize = (NPAGES as usize).div_ceil(64);
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum FrameError {
+    OutOfMemory,        // no free physical page is left
+    Misaligned,         // address is not page-aligned
+    OutOfRange,         // address is beyond MEMSIZE_PHYSICAL
+    InUse,              // page is already allocated
+    NotAllocated,       // page is free
+    InvalidOwner,       // owner is neither a `PageOwner` nor a valid pid
+}
+
+pub struct FrameAllocator {
+    free: [u64; NWORDS],    // bit `pn % 64` of word `pn / 64` set iff page `pn` is free
+    nfree: usize,           // number of free pages
+    sharers: [u16; NPAGES as usize],   // bit `pid` set iff process `pid` shares page `pn`
+}
+
+pub static mut FRAMES: FrameAllocator = FrameAllocator::new();
+
+impl Default for FrameAllocator {
+    fn default() -> Self {
+        FrameAllocator::new()
+    }
+}
+
+impl FrameAllocator {
+    pub const fn new() -> Self {
+        FrameAllocator { free: [0; NWORDS], nfree: 0, sharers: [0; NPAGES as usize] }
+    }
+
+    // init()
+    //    Build the free bitmap from `pageinfo[]`. Must run after
+    //    pageinfo_init().
+    /// # Safety
+    /// `pageinfo[]` must already be set up by pageinfo_init(); this discards
+    /// the state of every page the allocator knew about.
+    pub unsafe fn init(&mut self) {
+        self.free = [0; NWORDS];
+        self.nfree = 0;
+        self.sharers = [0; NPAGES as usize];
+        for (pn, info) in pageinfo.iter().enumerate() {
+            if info.refcount == 0 {
+                self.mark_free(pn);
+            }
+        }
+    }
+
+    // allocate(owner)
+    //    Allocate any free page to `owner` and return its physical address.
+    //    Lower addresses are handed out first.
+    /// # Safety
+    /// Writes the owner and refcount of the page in `pageinfo[]`. The allocator
+    /// must have been initialized with `init`.
+    pub unsafe fn allocate(&mut self, owner: i8) -> Result<usize, FrameError> {
+        check_owner(owner)?;
+        let word = self.free.iter().position(|&w| w != 0)
+            .ok_or(FrameError::OutOfMemory)?;
+        let pn = word * 64 + self.free[word].trailing_zeros() as usize;
+        self.claim(pn, owner);
+        Ok(page_address(pn as i32) as usize)
+    }
+
+    // allocate_at(addr, owner)
+    //    Allocate the page at physical address `addr` to `owner`.
+    /// # Safety
+    /// Writes the owner and refcount of the page in `pageinfo[]`. The allocator
+    /// must have been initialized with `init`.
+    pub unsafe fn allocate_at(&mut self, addr: usize, owner: i8) -> Result<(), FrameError> {
+        check_owner(owner)?;
+        let pn = page_index(addr)?;
+        if !self.is_free(pn) {
+            return Err(FrameError::InUse);
+        }
+        self.claim(pn, owner);
+        Ok(())
+    }
+
+    // allocate_contiguous(npages, owner)
+    //    Allocate `npages` physically contiguous pages to `owner` and
+    //    return the address of the first one. Meant for boot-time
+    //    reservations; takes the lowest run that fits.
+    /// # Safety
+    /// Writes `pageinfo[]` for every page of the run. The allocator must have
+    /// been initialized with `init`.
+    pub unsafe fn allocate_contiguous(&mut self, npages: usize, owner: i8) -> Result<usize, FrameError> {
+        check_owner(owner)?;
+        let mut run = 0;
+        for pn in 0..NPAGES as usize {
+            run = if self.is_free(pn) { run + 1 } else { 0 };
+            if run == npages {
+                let first = pn + 1 - npages;
+                for pn in first..=pn {
+                    self.claim(pn, owner);
+                }
+                return Ok(page_address(first as i32) as usize);
+            }
+        }
+        Err(FrameError::OutOfMemory)
+    }
+
+    // share(addr)
+    //    Add a reference to the allocated page at `addr`, for a mapping
+    //    that shares it.
+    /// # Safety
+    /// The new reference must correspond to a mapping of the page, or the page
+    /// is never freed.
+    pub unsafe fn share(&mut self, addr: usize) -> Result<(), FrameError> {
+        let pn = page_index(addr)?;
+        if self.is_free(pn) {
+            return Err(FrameError::NotAllocated);
+        }
+        pageinfo[pn] += 1;
+        Ok(())
+    }
+
+    // share_with(addr, pid)
+    //    Add a reference to the allocated page at `addr` for process `pid`,
+    //    which maps it besides its owner.
+    /// # Safety
+    /// The new reference must correspond to a mapping of the page in process
+    /// `pid`, which drops it with `unshare` and `free`.
+    pub unsafe fn share_with(&mut self, addr: usize, pid: i8) -> Result<(), FrameError> {
+        check_owner(pid)?;
+        self.share(addr)?;
+        self.sharers[page_number(addr as u64) as usize] |= 1 << pid;
+        Ok(())
+    }
+
+    // unshare(addr, pid)
+    //    Process `pid` no longer shares the page at `addr`. Its reference
+    //    must be dropped with `free`.
+    pub fn unshare(&mut self, addr: usize, pid: i8) -> Result<(), FrameError> {
+        check_owner(pid)?;
+        let pn = page_index(addr)?;
+        self.sharers[pn] &= !(1 << pid);
+        Ok(())
+    }
+
+    // sharer(addr)
+    //    Return one of the processes that share the page at `addr` besides
+    //    its owner, if any.
+    pub fn sharer(&self, addr: usize) -> Option<i8> {
+        let pn = page_index(addr).ok()?;
+        match self.sharers[pn] {
+            0 => None,
+            sharers => Some(sharers.trailing_zeros() as i8),
+        }
+    }
+
+    // free(addr)
+    //    Drop one reference to the page at `addr`. The page returns to the
+    //    free pool when its last reference goes away.
+    /// # Safety
+    /// The caller must own the reference it drops; once the last one is gone
+    /// the page may be handed out again while still mapped elsewhere.
+    pub unsafe fn free(&mut self, addr: usize) -> Result<(), FrameError> {
+        let pn = page_index(addr)?;
+        if self.is_free(pn) {
+            return Err(FrameError::NotAllocated);
+        }
+        pageinfo[pn].refcount -= 1;
+        if pageinfo[pn].refcount == 0 {
+            pageinfo[pn].owner = PageOwner::PoFree as i8;
+            self.sharers[pn] = 0;
+            self.mark_free(pn);
+        }
+        Ok(())
+    }
+
+    // transfer(addr, owner)
+    //    Make `owner` the owner of the allocated page at `addr`. If it
+    //    shared the page, it no longer counts as a sharer.
+    /// # Safety
+    /// Rewrites the owner in `pageinfo[]`; `owner` should map the page so that
+    /// it is released when `owner` exits.
+    pub unsafe fn transfer(&mut self, addr: usize, owner: i8) -> Result<(), FrameError> {
+        check_owner(owner)?;
+        let pn = page_index(addr)?;
+        if self.is_free(pn) {
+            return Err(FrameError::NotAllocated);
+        }
+        pageinfo[pn].owner = owner;
+        if owner > 0 {
+            self.sharers[pn] &= !(1 << owner);
+        }
+        Ok(())
+    }
+
+    // free_count()
+    //    Return the number of free physical pages.
+    pub fn free_count(&self) -> usize {
+        self.nfree
+    }
+
+    fn is_free(&self, pn: usize) -> bool {
+        self.free[pn / 64] & (1 << (pn % 64)) != 0
+    }
+
+    fn mark_free(&mut self, pn: usize) {
+        self.free[pn / 64] |= 1 << (pn % 64);
+        self.nfree += 1;
+    }
+
+    unsafe fn claim(&mut self, pn: usize, owner: i8) {
+        self.free[pn / 64] &= !(1 << (pn % 64));
+        self.nfree -= 1;
+        pageinfo[pn].owner = owner;
+        pageinfo[pn].refcount = 1;
+        self.sharers[pn] = 0;
+    }
+}
+
+// page_index(addr)
+//    Return the page number of physical address `addr`.
+
+fn page_index(addr: usize) -> Result<usize, FrameError> {
+    if !addr.is_multiple_of(PAGESIZE as usize) {
+        return Err(FrameError::Misaligned);
+    }
+    if addr >= MEMSIZE_PHYSICAL as usize {
+        return Err(FrameError::OutOfRange);
+    }
+    Ok(page_number(addr as u64) as usize)
+}
+
+// check_owner(owner)
+//    Pages belong to the kernel, to reserved memory, or to a process.
+
+fn check_owner(owner: i8) -> Result<(), FrameError> {
+    if owner == PageOwner::PoKernel as i8
+        || owner == PageOwner::PoReserved as i8
+        || (owner > 0 && (owner as usize) < NPROC) {
+        Ok(())
+    } else {
+        Err(FrameError::InvalidOwner)
+    }
+}
+
+// assign_physical_page(addr, owner)
+//    Allocates the page with physical address `addr` to the given owner.
+//    Fails if physical page `addr` was already allocated. Returns 0 on
+//    success and -1 on failure. C interface to `FRAMES.allocate_at`.
+
+/// # Safety
+/// Called from C with `pageinfo[]` and the frame allocator initialized.
+#[no_mangle]
+pub unsafe extern "C" fn assign_physical_page(addr: usize, owner: i8) -> core::ffi::c_int {
+    match FRAMES.allocate_at(addr, owner) {
+        Ok(()) => 0,
+        Err(_) => -1,
+    }
+}
@@ -0,0 +1,14 @@
+// This file is for linking C pageinfo functionality with Rust.
+// For better understanding of FFI consider reading this documentation.
+// https://doc.rust-lang.org/nomicon/ffi.html#foreign-calling-conventions
+
+#[allow(clippy::module_inception)]
+pub mod frame;
+
+use crate::kernel::kernel::PhysicalPageInfo;
+use crate::bindings::bindings_x86_64::*;
+use crate::bindings::bindings_kernel::*;
+
+extern "C-unwind" {
+    pub static mut pageinfo: [PhysicalPageInfo; NPAGES as usize];
+}
@@ -0,0 +1,219 @@
+// heap.rs
+//
+//    Kernel heap and global allocator.
+
+use crate::*;
+use crate::heap::*;
+use crate::aux::traits::*;
+use crate::frame::frame::FRAMES;
+use crate::kernel::kernel::PageOwner;
+use core::alloc::{GlobalAlloc, Layout};
+use core::ptr::null_mut;
+use spin::Mutex;
+
+// KERNEL HEAP
+//
+//    `heap_init()` reserves `HEAP_PAGES` physically contiguous pages from
+//    the frame allocator at boot. They are owned by the kernel and reached
+//    through the kernel's identity mapping, so the heap never needs page
+//    table changes. The pages are managed with a first-fit free list kept
+//    in address order; freed blocks are merged with their neighbors.
+//
+//    Every block is a multiple of `BLOCK_ALIGN` bytes and at least large
+//    enough to hold a `FreeBlock` header once it is freed.
+
+const HEAP_PAGES: usize = 16;
+const BLOCK_ALIGN: usize = 16;
+
+struct FreeBlock {
+    size: usize,
+    next: *mut FreeBlock,
+}
+
+// Allocation statistics, see `heap_stats()`.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct HeapStats {
+    pub size: usize,        // bytes managed by the heap
+    pub used: usize,        // bytes currently allocated
+    pub peak: usize,        // largest value `used` ever reached
+    pub allocs: usize,      // successful allocations so far
+    pub frees: usize,       // deallocations so far
+    pub failures: usize,    // allocations that could not be satisfied
+}
+
+struct Heap {
+    free_list: *mut FreeBlock,
+    stats: HeapStats,
+}
+
+unsafe impl Send for Heap {}
+
+pub struct KernelHeap {
+    heap: Mutex<Heap>,
+}
+
+#[global_allocator]
+static HEAP: KernelHeap = KernelHeap {
+    heap: Mutex::new(Heap {
+        free_list: null_mut(),
+        stats: HeapStats { size: 0, used: 0, peak: 0, allocs: 0, frees: 0, failures: 0 },
+    }),
+};
+
+// heap_init()
+//    Reserve the kernel heap pages and make them one big free block.
+//    Must run after the frame allocator is initialized.
+
+/// # Safety
+/// Must run once per boot, before anything is allocated. Any `Box` or `Vec`
+/// from before a soft reboot dangles afterwards and must not be dropped.
+pub unsafe fn heap_init() {
+    let size = HEAP_PAGES * PAGESIZE as usize;
+    let start = match FRAMES.allocate_contiguous(HEAP_PAGES, PageOwner::PoKernel as i8) {
+        Ok(start) => start,
+        Err(_) => c_panic!("heap_init: cannot reserve ", HEAP_PAGES, " pages"),
+    };
+
+    let block = start as *mut FreeBlock;
+    block.write(FreeBlock { size, next: null_mut() });
+
+    let mut heap = HEAP.heap.lock();
+    heap.free_list = block;
+    heap.stats = HeapStats { size, ..HeapStats::default() };
+}
+
+// heap_stats()
+//    Return a snapshot of the kernel heap's allocation statistics.
+
+pub fn heap_stats() -> HeapStats {
+    HEAP.heap.lock().stats
+}
+
+// block_size(layout)
+//    Return the number of bytes the heap sets aside for `layout`.
+
+fn block_size(layout: &Layout) -> usize {
+    layout.size()
+        .max(core::mem::size_of::<FreeBlock>())
+        .next_multiple_of(BLOCK_ALIGN)
+}
+
+impl Heap {
+    // allocate(layout)
+    //    Carve a block for `layout` out of the first free block that fits.
+    //    Leftovers in front of (because of alignment) and behind the block
+    //    stay on the free list.
+    unsafe fn allocate(&mut self, layout: Layout) -> *mut u8 {
+        let size = block_size(&layout);
+        let align = layout.align().max(BLOCK_ALIGN);
+
+        let mut prev: *mut FreeBlock = null_mut();
+        let mut block = self.free_list;
+        while !block.is_null() {
+            let start = block as usize;
+            let end = start + (*block).size;
+            let mut addr = start.next_multiple_of(align);
+            // leftover space in front must be big enough to stay a free block
+            if addr != start && addr - start < core::mem::size_of::<FreeBlock>() {
+                addr = (start + core::mem::size_of::<FreeBlock>()).next_multiple_of(align);
+            }
+
+            if addr + size <= end {
+                let next = (*block).next;
+                let mut link = next;
+
+                // leftover behind the allocation
+                let tail = end - (addr + size);
+                if tail >= core::mem::size_of::<FreeBlock>() {
+                    let rest = (addr + size) as *mut FreeBlock;
+                    rest.write(FreeBlock { size: tail, next });
+                    link = rest;
+                }
+
+                // leftover in front of the allocation
+                if addr != start {
+                    (*block).size = addr - start;
+                    (*block).next = link;
+                } else if prev.is_null() {
+                    self.free_list = link;
+                } else {
+                    (*prev).next = link;
+                }
+
+                let used = if tail >= core::mem::size_of::<FreeBlock>() { size } else { size + tail };
+                self.stats.used += used;
+                self.stats.peak = self.stats.peak.max(self.stats.used);
+                self.stats.allocs += 1;
+                return addr as *mut u8;
+            }
+
+            prev = block;
+            block = (*block).next;
+        }
+
+        self.stats.failures += 1;
+        null_mut()
+    }
+
+    // deallocate(ptr, layout)
+    //    Put the block at `ptr` back on the free list, merging it with the
+    //    free blocks right before and after it.
+    unsafe fn deallocate(&mut self, ptr: *mut u8, layout: Layout) {
+        let addr = ptr as usize;
+        let mut size = block_size(&layout);
+
+        // find the free blocks around `addr`
+        let mut prev: *mut FreeBlock = null_mut();
+        let mut next = self.free_list;
+        while !next.is_null() && (next as usize) < addr {
+            prev = next;
+            next = (*next).next;
+        }
+
+        // a leftover too small to be a free block was handed out with the
+        // allocation; take it back as well
+        if !next.is_null() && (next as usize) - (addr + size) < core::mem::size_of::<FreeBlock>() {
+            size = next as usize - addr;
+        }
+        self.stats.used -= size;
+        self.stats.frees += 1;
+
+        let block = addr as *mut FreeBlock;
+        block.write(FreeBlock { size, next });
+        if !next.is_null() && addr + size == next as usize {
+            (*block).size += (*next).size;
+            (*block).next = (*next).next;
+        }
+        if prev.is_null() {
+            self.free_list = block;
+        } else if prev as usize + (*prev).size == addr {
+            (*prev).size += (*block).size;
+            (*prev).next = (*block).next;
+        } else {
+            (*prev).next = block;
+        }
+    }
+}
+
+unsafe impl GlobalAlloc for KernelHeap {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        self.heap.lock().allocate(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        self.heap.lock().deallocate(ptr, layout)
+    }
+}
+
+// alloc_error(layout)
+//    Called when an allocation through `alloc::` types fails. The kernel
+//    has no way to recover from that, so report the request and stop.
+
+#[alloc_error_handler]
+fn alloc_error(layout: Layout) -> ! {
+    let stats = heap_stats();
+    unsafe {
+        c_panic!("kernel heap exhausted: ", layout.size(), " bytes requested, ",
+                 stats.used, " of ", stats.size, " in use");
+    }
+}
@@ -0,0 +1,8 @@
+// The kernel heap sits on top of the frame allocator and
+// backs `alloc::boxed::Box`, `alloc::vec::Vec`, `alloc::string::String`.
+// https://doc.rust-lang.org/alloc/alloc/trait.GlobalAlloc.html
+
+#[allow(clippy::module_inception)]
+pub mod heap;
+
+use crate::bindings::bindings_x86_64::*;
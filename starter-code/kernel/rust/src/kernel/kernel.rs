@@ -4,25 +4,88 @@
 
 use crate::*;
 use crate::kernel::*;
+use crate::vm::vm::{copy_pagetable, free_pagetable, virtual_memory_lookup, map_range, unmap_range, protect_range, translate};
+use crate::vm::vma::{VMAS, prot_flags, prot_valid, vma_init};
+use crate::vm::shm::{shm_attach, shm_create, shm_detach, shm_exit, shm_fork, shm_init, SEGMENTS};
+use crate::vm::pagetable::{PageTable, PageTableError, PteFlags, VirtAddr};
+use crate::frame::frame::FRAMES;
+use crate::heap::heap::heap_init;
+use crate::kloader::kloader::program_load;
+use crate::kloader::programs::find_program;
+use crate::sched::sched::{run, schedule, sched_init, scheduler};
+use crate::sched::timer::{sleep, timer_tick, SLEEP_MAX, TIMERS};
 use crate::aux::traits::*;
 use core::ptr::NonNull;
+use alloc::string::String;
+use alloc::vec::Vec;
 use core::ops::AddAssign;
 
 // INITIAL PHYSICAL MEMORY LAYOUT
 //
 //  +-------------- Base Memory --------------+
 //  v                                         v
-// +-----+--------------------+----------------+--------------------+---------/
-// |     | Kernel      Kernel |       :    I/O | App 1        App 1 | App 2
-// |     | Code + Data  Stack |  ...  : Memory | Code + Data  Stack | Code ...
-// +-----+--------------------+----------------+--------------------+---------/
-// 0  0x40000              0x80000 0xA0000 0x100000             0x140000
+// +-----+--------------------+----------------+------------------------------/
+// |     | Kernel      Kernel |       :    I/O | Free pages for page tables,
+// |     | Code + Data  Stack |  ...  : Memory | app code, data, heap, stack
+// +-----+--------------------+----------------+------------------------------/
+// 0  0x40000              0x80000 0xA0000 0x100000
 //                                             ^
-//                                             | \___ PROC_SIZE ___/
+//                                             |
 //                                      PROC_START_ADDR
+//
+// PROCESS VIRTUAL MEMORY LAYOUT
+//
+//    Every process uses the same virtual layout, and any free physical page
+//    can back any of its virtual pages.
+//
+// +--------------------------+--------------+--- ... ---+-------+-----------+
+// | Kernel (no PTE_U)        | App          | Heap ->   | Guard | <- Stack  |
+// | except for the console   | Code + Data  |           | page  |           |
+// +--------------------------+--------------+--- ... ---+-------+-----------+
+// 0                   PROC_START_ADDR                            MEMSIZE_VIRTUAL
+//                                                               \_ STACK_ _/
+//                                                                SIZE_MAX
 
-const PROC_SIZE: usize = 0x40000;   // initial state only
 const HZ: u32 = 100;                // timer interrupt frequency (interrupts/sec)
+pub const STACK_SIZE_MAX: u64 = 0x10000; // stacks grow on demand up to this size
+const PROGRAM_NAME_MAX: usize = 32;  // longest program name sys_exec accepts, with the NUL
+const ARG_MAX: usize = 128;          // longest argument sys_exec accepts, with the NUL
+const ARGV_MAX: usize = 16;          // most arguments a process can get
+const ARGS_SIZE_MAX: usize = (PAGESIZE / 2) as usize; // room for arguments on the initial stack
+
+// BOOT COMMANDS
+//
+//    The boot command (see `check_keyboard`) says which programs to start,
+//    as processes 1, 2, ... in order. Each is given as a command line: the
+//    program name (see programs.rs) followed by its arguments, separated
+//    by spaces; the words become the process's `argv`. A command listed
+//    here stands for its command lines, any other command is a command
+//    line itself. Without a command, or if the command names no program,
+//    the allocators run. A leading `sched=NAME` selects the scheduling
+//    policy first (see sched.rs).
+
+const BOOT_COMMANDS: &[(&str, &[&str])] = &[
+    ("allocator", &["allocator", "allocator", "allocator", "allocator"]),
+    ("test2", &["test 1", "test 2"]),
+    ("nice", &["allocator 39", "allocator", "allocator", "allocator"]),
+];
+
+// boot_command_lines(command)
+//    Return the command lines to start for boot command `command`.
+
+fn boot_command_lines(command: Option<&str>) -> Vec<&str> {
+    let default = BOOT_COMMANDS[0].1;
+    let Some(command) = command else {
+        return default.to_vec();
+    };
+    if let Some(&(_, lines)) = BOOT_COMMANDS.iter().find(|&&(c, _)| c == command) {
+        return lines.to_vec();
+    }
+    match command.split_whitespace().next().and_then(find_program) {
+        Some(_) => alloc::vec![command],
+        None => default.to_vec(),
+    }
+}
 
 // PAGEINFO
 //
@@ -72,6 +135,10 @@ pub enum PageOwner {
 pub unsafe extern "sysv64" fn kernel(command: Option<NonNull<u8>>) {
     hardware_init();
     pageinfo_init();
+    FRAMES.init();
+    vma_init();
+    shm_init();
+    heap_init();
     console_clear();
     timer_init(HZ);
 
@@ -85,55 +152,919 @@ pub unsafe extern "sysv64" fn kernel(command: Option<NonNull<u8>>) {
         processes[i].p_state = P_FREE;
     }
 
-    match command {
-        Some(ptr) => {
-            let cmd = ptr.as_ptr() as *const core::ffi::c_char;
+    let command = command.and_then(|ptr| {
+        core::ffi::CStr::from_ptr(ptr.as_ptr() as *const core::ffi::c_char).to_str().ok()
+    });
+    let command = sched_init(command);
+    for (i, line) in boot_command_lines(command).iter().enumerate() {
+        let argv: Vec<&str> = line.split_whitespace().collect();
+        process_setup(i + 1, &argv);
+    }
 
-            if strcmp(cmd, b"fork\0".as_ptr() as *const i8) == 0 {
-                process_setup(1, 4);
-            } else if strcmp(cmd, b"forkexit\0".as_ptr() as *const i8) == 0 {
-                process_setup(1, 5);
-            } else if strcmp(cmd, b"test\0".as_ptr() as *const i8) == 0 {
-                process_setup(1, 6);
-            } else if strcmp(cmd, b"test2\0".as_ptr() as *const i8) == 0 {
-                for i in 1..=2 {
-                    process_setup(i, 6);
-                }
-            } else {
-                for i in 1..=4 {
-                    process_setup(i, i - 1);
-                }
+    // a program that failed to load left its slot free
+    match (1..NPROC).find(|&pid| processes[pid].p_state == P_RUNNABLE) {
+        Some(pid) => run(&mut processes[pid]),
+        None => c_panic!("No process could be loaded!"),
+    }
+}
+
+// process_setup(pid, argv)
+//    Load the application program called `argv[0]` as process number
+//    `pid`. This loads the application's code and data into memory, sets
+//    its %rip and %rsp, gives it a stack page holding `argv` (see
+//    `process_stack_init`), and marks it as runnable.
+//    The stack may later grow down to `STACK_SIZE_MAX`, with an unmapped
+//    guard page below it (see `handle_stack_fault`).
+//
+//    If the program cannot be loaded, the slot is freed again and false is
+//    returned; the other processes are not affected.
+
+pub unsafe fn process_setup(pid: usize, argv: &[&str]) -> bool {
+    process_init(&mut processes[pid], 0);
+    processes[pid].p_priority = PRIO_DEFAULT;
+    processes[pid].p_ppid = 0;
+    processes[pid].p_ticks = 0;
+    processes[pid].p_start_ticks = ticks;
+    processes[pid].p_tickets = TICKETS_DEFAULT;
+    processes[pid].p_pagetable = copy_pagetable(pid as i8);
+    assert!(!processes[pid].p_pagetable.is_null());
+
+    if program_load(&mut processes[pid], argv[0]) < 0 {
+        c_console!("Can't load program ", argv[0], " as process ", pid, "!");
+        process_free(pid);
+        return false;
+    }
+    if !process_stack_init(&mut processes[pid], argv) {
+        c_console!("Can't set up the stack of process ", pid, "!");
+        process_free(pid);
+        return false;
+    }
+    processes[pid].p_state = P_RUNNABLE;
+    scheduler().on_wake(pid);
+    true
+}
+
+// process_stack_init(p, argv)
+//    Map the initial stack page of process `p` at the top of user memory
+//    and put the argument vector `argv` on it, so that the program starts
+//    as a call to `process_main(argc, argv)`: %rdi holds `argc` and %rsi
+//    points to the `argv` array. The stack may later grow down to
+//    `p_stack_limit`.
+//
+//    The top of the stack looks like this, following the SysV ABI: the
+//    `argv` array is 16-byte aligned and %rsp points right below it, as
+//    if `process_main` had just been called. It must not return.
+//
+//        MEMSIZE_VIRTUAL ->  +----------------+
+//                            | argv[] strings |
+//                            +----------------+
+//                            | (padding)      |
+//                            | NULL           |
+//                            | argv[argc - 1] |
+//                            | ...            |
+//                  %rsi ->   | argv[0]        |  16-byte aligned
+//                  %rsp ->   | 0              |  return address
+//
+//    Returns false if there is no memory for the stack page, or if `argv`
+//    has more than ARGV_MAX entries or takes more than ARGS_SIZE_MAX bytes.
+
+/// # Safety
+/// `p.p_pagetable` must be a valid page table owned by `p` with no stack mapped
+/// yet.
+pub unsafe fn process_stack_init(p: &mut Proc, argv: &[&str]) -> bool {
+    let strings: usize = argv.iter().map(|arg| arg.len() + 1).sum();
+    let pointers = (argv.len() + 1) * core::mem::size_of::<u64>();
+    if argv.len() > ARGV_MAX || strings + pointers + 16 + 8 > ARGS_SIZE_MAX {
+        return false;
+    }
+
+    let top = MEMSIZE_VIRTUAL as usize;
+    let stack_page = top - PAGESIZE as usize;
+    let pa = match FRAMES.allocate(p.p_pid as i8) {
+        Ok(pa) => pa,
+        Err(_) => return false,
+    };
+    core::ptr::write_bytes(pa as *mut u8, 0, PAGESIZE as usize);
+    if virtual_memory_map(p.p_pagetable, stack_page, pa,
+                          PAGESIZE as usize, (PTE_P | PTE_W | PTE_U) as i32) < 0 {
+        FRAMES.free(pa).unwrap();
+        return false;
+    }
+    // the page is written through the kernel's identity mapping
+    let at = |va: usize| (pa + va - stack_page) as *mut u8;
+
+    let mut string_va = top - strings;
+    let argv_va = (string_va - pointers) & !15;
+    for (i, arg) in argv.iter().enumerate() {
+        (at(argv_va) as *mut u64).add(i).write(string_va as u64);
+        arg.as_ptr().copy_to_nonoverlapping(at(string_va), arg.len());
+        string_va += arg.len() + 1;     // the page is zeroed, so the NUL is there
+    }
+
+    p.p_registers.reg_rdi = argv.len() as u64;
+    p.p_registers.reg_rsi = argv_va as u64;
+    p.p_registers.reg_rsp = (argv_va - 8) as u64;
+    p.p_stack_bottom = stack_page as u64;
+    p.p_stack_limit = MEMSIZE_VIRTUAL - STACK_SIZE_MAX;
+    true
+}
+
+// page_reassign(addr)
+//    The shared page at `addr` lost the reference held by its owner. Hand
+//    ownership to one of the processes that still map it (see
+//    `FRAMES.sharer`), so that `pageinfo[]` never names a dead process as
+//    the owner of a live page.
+
+/// # Safety
+/// `addr` must be a user page whose owner has already dropped its reference.
+pub unsafe fn page_reassign(addr: usize) {
+    if let Some(pid) = FRAMES.sharer(addr) {
+        FRAMES.transfer(addr, pid).unwrap();
+    }
+}
+
+// page_release(addr, pid)
+//    Drop the reference process `pid` holds on the user page at `addr`.
+//    The page is freed once nobody maps it; if it is still shared and `pid`
+//    owned it, ownership moves to one of the remaining sharers.
+
+/// # Safety
+/// Process `pid` must hold a reference to `addr` and already have removed its
+/// mapping of it.
+pub unsafe fn page_release(addr: usize, pid: PidT) {
+    let pn = page_number(addr as u64) as usize;
+    FRAMES.free(addr).unwrap();
+    if pageinfo[pn].refcount == 0 {
+        return;
+    }
+    if pageinfo[pn].owner == pid as i8 {
+        page_reassign(addr);
+    } else {
+        FRAMES.unshare(addr, pid as i8).unwrap();
+    }
+}
+
+// process_free(pid)
+//    Release the address space of process `pid`. Drops a reference to every
+//    user page mapped in its page table, freeing the pages nobody else maps
+//    and handing the shared ones it owned to a remaining sharer. Then frees
+//    the page table pages themselves and the mmap regions, detaches its
+//    shared memory segments, and marks the slot `P_FREE` so it can be
+//    reused.
+//
+//    Every page mapped at or above PROC_START_ADDR belongs to the process,
+//    including PROT_NONE pages that lack PTE_U.
+
+/// # Safety
+/// Process `pid` must not be running, and nothing may use its page table
+/// afterwards.
+pub unsafe fn process_free(pid: usize) {
+    let pagetable = processes[pid].p_pagetable;
+    shm_exit(pid as PidT, pagetable);
+    free_user_memory(pid, pagetable);
+    VMAS[pid].clear();
+
+    if processes[pid].p_state == P_RUNNABLE {
+        scheduler().on_block(pid);
+    }
+    if processes[pid].p_state == P_BLOCKED {
+        TIMERS.cancel(pid);
+    }
+    processes[pid].p_pagetable = core::ptr::null_mut();
+    processes[pid].p_state = P_FREE;
+
+    // orphans have no parent to change their priority
+    for child in processes.iter_mut().filter(|child| child.p_ppid == pid as PidT) {
+        child.p_ppid = 0;
+    }
+}
+
+// free_user_memory(pid, pagetable)
+//    Release every page that `pagetable`, a page table of process `pid`,
+//    maps at or above PROC_START_ADDR, then free the page table itself.
+
+/// # Safety
+/// `pagetable` must belong to process `pid` and not be loaded in %cr3.
+pub unsafe fn free_user_memory(pid: usize, pagetable: *mut x86_64_pagetable) {
+    unmap_range(pagetable, PROC_START_ADDR as usize,
+                (MEMSIZE_VIRTUAL - PROC_START_ADDR) as usize, |_, pe| {
+        page_release(pe.addr().as_usize(), pid as PidT);
+    }).unwrap();
+    free_pagetable(pagetable);
+}
+
+// copy_from_user(p, addr, buf)
+//    Fill `buf` with the bytes at `addr` in process `p`. Returns false if
+//    some of them are not readable by `p`.
+
+/// # Safety
+/// `p.p_pagetable` must be a valid page table.
+pub unsafe fn copy_from_user(p: &Proc, addr: usize, buf: &mut [u8]) -> bool {
+    let perm = (PTE_P | PTE_U) as i32;
+    for (i, byte) in buf.iter_mut().enumerate() {
+        let Some(va) = addr.checked_add(i) else {
+            return false;
+        };
+        let vam = virtual_memory_lookup(p.p_pagetable, va);
+        if vam.pn < 0 || vam.perm & perm != perm {
+            return false;
+        }
+        // physical memory is identity-mapped in the kernel
+        *byte = *(vam.pa as *const u8);
+    }
+    true
+}
+
+// copy_string_from_user(p, addr, buf)
+//    Copy the NUL-terminated string at `addr` in process `p` into `buf`
+//    and return it. Every byte up to the NUL must be readable by `p`.
+//
+//    Returns `None` if it is not, if the string does not fit in `buf`, or
+//    if it is not valid UTF-8.
+
+/// # Safety
+/// `p.p_pagetable` must be a valid page table.
+pub unsafe fn copy_string_from_user<'a>(p: &Proc, addr: usize, buf: &'a mut [u8]) -> Option<&'a str> {
+    for i in 0..buf.len() {
+        if !copy_from_user(p, addr.checked_add(i)?, &mut buf[i..=i]) {
+            return None;
+        }
+        if buf[i] == 0 {
+            return core::str::from_utf8(&buf[..i]).ok();
+        }
+    }
+    None
+}
+
+// syscall_page_alloc(p, addr)
+//    Map a fresh zeroed page at virtual address `addr` in process `p`. Any
+//    free physical page will do. `addr` must be page-aligned, lie in
+//    `[PROC_START_ADDR, MEMSIZE_VIRTUAL)`, be unmapped, and stay clear of
+//    the stack's growth region and guard page and of mmap regions.
+//
+//    Returns 0 on success and -1 on failure.
+
+/// # Safety
+/// `p` must be the current process, with a valid page table.
+pub unsafe fn syscall_page_alloc(p: &mut Proc, addr: u64) -> i32 {
+    if !addr.is_multiple_of(PAGESIZE)
+        || !(PROC_START_ADDR..MEMSIZE_VIRTUAL).contains(&addr)
+        || (addr >= p.p_stack_limit - PAGESIZE && addr < p.p_stack_bottom)
+        || VMAS[p.p_pid as usize].find(addr as usize).is_some()
+        || virtual_memory_lookup(p.p_pagetable, addr as usize).pn >= 0 {
+        return -1;
+    }
+    let pa = match FRAMES.allocate(p.p_pid as i8) {
+        Ok(pa) => pa,
+        Err(_) => return -1,
+    };
+    core::ptr::write_bytes(pa as *mut u8, 0, PAGESIZE as usize);
+    if virtual_memory_map(p.p_pagetable, addr as usize, pa, PAGESIZE as usize,
+                          (PTE_P | PTE_W | PTE_U) as i32) < 0 {
+        FRAMES.free(pa).unwrap();
+        return -1;
+    }
+    0
+}
+
+// syscall_mapping(p)
+//    Store the `vamapping` of the address in %rsi at the address in %rdi,
+//    which the process must be able to write. Addresses the walk cannot
+//    reach because a page table above L1 is missing are reported in the
+//    log: they lie outside the process's page table rather than in an
+//    unmapped page.
+
+/// # Safety
+/// `p` must be the process whose registers hold the arguments, with a valid
+/// page table.
+pub unsafe fn syscall_mapping(p: &mut Proc) {
+    let mapping_ptr = p.p_registers.reg_rdi as usize;
+    let ptr = p.p_registers.reg_rsi;
+
+    // both ends of the destination must be writable by the process
+    let perm = (PTE_P | PTE_W | PTE_U) as i32;
+    let end_ptr = mapping_ptr + core::mem::size_of::<VAMapping>() - 1;
+    let map = virtual_memory_lookup(p.p_pagetable, mapping_ptr);
+    let end_map = virtual_memory_lookup(p.p_pagetable, end_ptr);
+    if map.perm & perm != perm || end_map.perm & perm != perm {
+        return;
+    }
+
+    let pt = PageTable::from_raw(p.p_pagetable).unwrap();
+    let lookup = match pt.translate(VirtAddr::new(ptr)) {
+        Ok(t) => t.to_vamapping(),
+        Err(e) => {
+            if let PageTableError::MissingTable(_) = e {
+                c_log!("sys_mapping: ", ptr as *const u8, ": ", e, "\n");
             }
+            VAMapping::default()
         }
-        None => {
-            for i in 1..=4 {
-                process_setup(i, i - 1);
+    };
+    // physical memory is identity-mapped in the kernel
+    (map.pa as *mut VAMapping).write_unaligned(lookup);
+}
+
+// syscall_fork(parent)
+//    Create a copy of `parent` in a free process slot. User pages are not
+//    copied: the child maps the parent's physical pages at the same virtual
+//    addresses, and `pageinfo[].refcount` counts the sharers. Writable pages
+//    are downgraded to read-only `PTE_COW` mappings in both processes, so
+//    the first write to one is resolved by `handle_cow_fault`; shared
+//    memory segments stay writable and shared. The child inherits the
+//    parent's mmap regions and segment attachments and resumes with the
+//    parent's registers, except that it sees 0 as the return value, and
+//    its priority and tickets.
+//
+//    Returns the child's pid, or -1 if there is no free slot or physical
+//    memory runs out (in which case the child is torn down again).
+
+/// # Safety
+/// `parent` must be the current process. Must run with interrupts disabled, as
+/// it picks and fills a free slot of `processes`.
+pub unsafe fn syscall_fork(parent: &mut Proc) -> PidT {
+    let pid = match (1..NPROC).find(|&i| processes[i].p_state == P_FREE) {
+        Some(pid) => pid,
+        None => return -1,
+    };
+
+    let pagetable = copy_pagetable(pid as i8);
+    if pagetable.is_null() {
+        return -1;
+    }
+    processes[pid] = *parent;
+    // not runnable until it is fully set up
+    processes[pid].p_state = P_FREE;
+    processes[pid].p_pid = pid as PidT;
+    processes[pid].p_ppid = parent.p_pid;
+    processes[pid].p_ticks = 0;
+    processes[pid].p_start_ticks = ticks;
+    processes[pid].p_pagetable = pagetable;
+
+    let mut va = PROC_START_ADDR as usize;
+    while va < MEMSIZE_VIRTUAL as usize {
+        if let Ok(t) = translate(parent.p_pagetable, va) {
+            let pa = t.pa.as_usize();
+            let mut flags = t.flags;
+            if flags.contains(PteFlags::WRITABLE) && !flags.contains(PteFlags::SHM) {
+                flags = (flags - PteFlags::WRITABLE) | PteFlags::COW;
+                map_range(parent.p_pagetable, va, pa, PAGESIZE as usize, flags).unwrap();
             }
+            if map_range(pagetable, va, pa, PAGESIZE as usize, flags).is_err() {
+                process_free(pid);
+                return -1;
+            }
+            FRAMES.share_with(pa, pid as i8).unwrap();
         }
+        va += PAGESIZE as usize;
     }
 
-    run(&mut processes[1]);
+    VMAS[pid].clone_from(&VMAS[parent.p_pid as usize]);
+    shm_fork(parent.p_pid, pid as PidT);
+
+    processes[pid].p_registers.reg_rax = 0;
+    processes[pid].p_state = P_RUNNABLE;
+    scheduler().on_wake(pid);
+    pid as PidT
 }
 
-// process_setup(pid, program_number)
-//    Load application program `program_number` as process number `pid`.
-//    This loads the application's code and data into memory, sets its
-//    %rip and %rsp, gives it a stack page, and marks it as runnable.
+// handle_cow_fault(p, addr)
+//    Resolve a write fault at `addr` on a copy-on-write page of process `p`.
+//    While other processes still share the page, `p` gets a private copy
+//    of it; the last sharer simply takes ownership and gets its write
+//    permission back.
+//
+//    Returns false if `addr` is not a copy-on-write page or there is no
+//    memory left for the copy.
 
-pub unsafe fn process_setup(pid: usize, pn: usize) {
-    process_init(&mut processes[pid], 0);
-    processes[pid].p_pagetable = kernel_pagetable;
-    pageinfo[page_number(kernel_pagetable as u64) as usize].refcount += 1; //increase refcount since kernel_pagetable was used
+/// # Safety
+/// `p` must be the faulting process and `addr` the faulting address.
+pub unsafe fn handle_cow_fault(p: &mut Proc, addr: usize) -> bool {
+    let va = addr & !(PAGE_OFF_MASK as usize);
+    let t = match translate(p.p_pagetable, va) {
+        Ok(t) if t.flags.contains(PteFlags::COW) => t,
+        _ => return false,
+    };
+    let flags = (t.flags - PteFlags::COW) | PteFlags::WRITABLE;
+    let old_pa = t.pa.as_usize();
 
-    let r = program_load(&mut processes[pid], pn as i32, core::ptr::null());
-    assert!(r >= 0); 
+    if pageinfo[page_number(old_pa as u64) as usize].refcount == 1 {
+        FRAMES.transfer(old_pa, p.p_pid as i8).unwrap();
+        map_range(p.p_pagetable, va, old_pa, PAGESIZE as usize, flags).unwrap();
+        return true;
+    }
 
-    processes[pid].p_registers.reg_rsp = PROC_START_ADDR + (PROC_SIZE * pid) as u64;
-    let stack_page = (processes[pid].p_registers.reg_rsp - PAGESIZE) as usize;
-    assign_physical_page(stack_page, pid as i8);
-    virtual_memory_map(processes[pid].p_pagetable, stack_page, stack_page, 
-                PAGESIZE as usize, (PTE_P | PTE_W | PTE_U) as i32);
-    processes[pid].p_state = P_RUNNABLE;
+    let pa = match FRAMES.allocate(p.p_pid as i8) {
+        Ok(pa) => pa,
+        Err(_) => return false,
+    };
+    memcpy(
+        pa as *mut core::ffi::c_void,
+        old_pa as *const core::ffi::c_void,
+        PAGESIZE as usize,
+    );
+    map_range(p.p_pagetable, va, pa, PAGESIZE as usize, flags).unwrap();
+    page_release(old_pa, p.p_pid);
+    true
+}
+
+// handle_stack_fault(p, addr)
+//    Grow the stack of process `p` down to cover `addr`, a missing page
+//    below the current stack extent `p_stack_bottom`. The stack may grow
+//    down to `p_stack_limit`; the page right below the limit is a guard page
+//    that is never mapped, so running off the stack still faults. New stack
+//    pages are zeroed.
+//
+//    Returns false if `addr` is outside the growth region or memory runs
+//    out (the pages mapped so far are kept).
+
+/// # Safety
+/// `p` must be the faulting process and `addr` the faulting address.
+pub unsafe fn handle_stack_fault(p: &mut Proc, addr: usize) -> bool {
+    if addr < p.p_stack_limit as usize || addr >= p.p_stack_bottom as usize {
+        return false;
+    }
+    let bottom = addr & !(PAGE_OFF_MASK as usize);
+    while p.p_stack_bottom as usize > bottom {
+        let va = p.p_stack_bottom as usize - PAGESIZE as usize;
+        let pa = match FRAMES.allocate(p.p_pid as i8) {
+            Ok(pa) => pa,
+            Err(_) => return false,
+        };
+        core::ptr::write_bytes(pa as *mut u8, 0, PAGESIZE as usize);
+        if virtual_memory_map(p.p_pagetable, va, pa, PAGESIZE as usize,
+                              (PTE_P | PTE_W | PTE_U) as i32) < 0 {
+            FRAMES.free(pa).unwrap();
+            return false;
+        }
+        p.p_stack_bottom = va as u64;
+    }
+    true
+}
+
+// is_stack_guard(p, addr)
+//    Return true if `addr` falls in the guard page below the stack of `p`.
+
+pub fn is_stack_guard(p: &Proc, addr: usize) -> bool {
+    let guard = (p.p_stack_limit - PAGESIZE) as usize;
+    addr >= guard && addr < p.p_stack_limit as usize
+}
+
+// process_set_brk(p, brk)
+//    Move the program break of process `p` to `brk`. Heap pages are mapped
+//    for `[p_heap_start, brk)` rounded up to whole pages: growing the heap
+//    maps fresh zeroed pages, shrinking it releases the pages past the new
+//    break. The break may not drop below `p_heap_start` or reach the stack's
+//    guard page, and the new heap range must not already be mapped or
+//    belong to an mmap region.
+//
+//    Returns false (leaving the heap untouched) if `brk` is out of bounds,
+//    overlaps an existing mapping, or memory runs out.
+
+/// # Safety
+/// `p` must have been loaded by `program_load`, which sets `p_heap_start`.
+pub unsafe fn process_set_brk(p: &mut Proc, brk: u64) -> bool {
+    if brk < p.p_heap_start || brk > p.p_stack_limit - PAGESIZE {
+        return false;
+    }
+    let old_end = roundup(p.p_brk as usize, PAGESIZE as usize);
+    let new_end = roundup(brk as usize, PAGESIZE as usize);
+
+    // grow: check the new range is free before taking any memory
+    if VMAS[p.p_pid as usize].overlaps(old_end, new_end) {
+        return false;
+    }
+    let mut va = old_end;
+    while va < new_end {
+        if virtual_memory_lookup(p.p_pagetable, va).pn >= 0 {
+            return false;
+        }
+        va += PAGESIZE as usize;
+    }
+    let mut va = old_end;
+    while va < new_end {
+        let pa = match FRAMES.allocate(p.p_pid as i8) {
+            Ok(pa) => pa,
+            Err(_) => {
+                process_unmap(p, old_end, va - old_end);
+                return false;
+            }
+        };
+        core::ptr::write_bytes(pa as *mut u8, 0, PAGESIZE as usize);
+        if virtual_memory_map(p.p_pagetable, va, pa, PAGESIZE as usize,
+                              (PTE_P | PTE_W | PTE_U) as i32) < 0 {
+            FRAMES.free(pa).unwrap();
+            process_unmap(p, old_end, va - old_end);
+            return false;
+        }
+        va += PAGESIZE as usize;
+    }
+
+    // shrink: release the pages above the new break
+    if new_end < old_end {
+        process_unmap(p, new_end, old_end - new_end);
+    }
+
+    p.p_brk = brk;
+    true
+}
+
+// process_unmap(p, va, sz)
+//    Remove the user mappings for `[va, va + sz)` from process `p` and
+//    release the pages they referred to.
+
+/// # Safety
+/// Every page in the range must be a page of `p` that `page_release` may drop;
+/// page tables are not released.
+pub unsafe fn process_unmap(p: &mut Proc, va: usize, sz: usize) {
+    let pid = p.p_pid;
+    unmap_range(p.p_pagetable, va, sz, |_, pe| {
+        page_release(pe.addr().as_usize(), pid);
+    }).unwrap();
+}
+
+// syscall_mmap(p, len, prot)
+//    Give process `p` a region of `len` bytes (rounded up to whole pages)
+//    of anonymous memory with protection `prot`. The kernel picks the
+//    highest free range between the program break and the stack's guard
+//    page that does not touch other regions or mapped pages. Nothing is
+//    mapped yet: `handle_vma_fault` zero-fills pages on first access.
+//
+//    Returns the start of the region, or `u64::MAX` ((void *) -1).
+
+/// # Safety
+/// `p` must be the current process, with a valid page table.
+pub unsafe fn syscall_mmap(p: &mut Proc, len: u64, prot: i32) -> u64 {
+    if len == 0 || len > MEMSIZE_VIRTUAL || !prot_valid(prot) {
+        return u64::MAX;
+    }
+    let len = roundup(len as usize, PAGESIZE as usize);
+    let lo = roundup(p.p_brk as usize, PAGESIZE as usize);
+    let hi = (p.p_stack_limit - PAGESIZE) as usize;
+    let pagetable = p.p_pagetable;
+
+    let vmas = &mut VMAS[p.p_pid as usize];
+    match vmas.find_gap(lo, hi, len, |va| virtual_memory_lookup(pagetable, va).pn >= 0) {
+        Some(start) => {
+            vmas.insert(start, start + len, prot);
+            start as u64
+        }
+        None => u64::MAX,
+    }
+}
+
+// syscall_munmap(p, addr, len)
+//    Remove `[addr, addr + len)` from the mmap regions of process `p` and
+//    release the pages mapped there. Parts of the range outside the
+//    regions are left alone.
+//
+//    Returns 0 on success and -1 if the range is misaligned or outside
+//    process memory.
+
+/// # Safety
+/// `p` must be the current process, with a valid page table.
+pub unsafe fn syscall_munmap(p: &mut Proc, addr: u64, len: u64) -> i32 {
+    if !addr.is_multiple_of(PAGESIZE) || len == 0
+        || addr < PROC_START_ADDR || len > MEMSIZE_VIRTUAL - addr {
+        return -1;
+    }
+    let start = addr as usize;
+    let end = start + roundup(len as usize, PAGESIZE as usize);
+
+    let pid = p.p_pid as usize;
+    let mut i = 0;
+    while let Some(vma) = VMAS[pid].iter().nth(i).copied() {
+        if vma.start < end && start < vma.end {
+            let (lo, hi) = (vma.start.max(start), vma.end.min(end));
+            process_unmap(p, lo, hi - lo);
+        }
+        i += 1;
+    }
+    VMAS[pid].remove(start, end);
+    0
+}
+
+// syscall_mprotect(p, addr, len, prot)
+//    Change the protection of `[addr, addr + len)` in process `p` to
+//    `prot`. The range must be covered by mmap regions. Pages already
+//    mapped are updated too; a writable page that is still shared after
+//    fork becomes a copy-on-write page instead.
+//
+//    Returns 0 on success and -1 on failure.
+
+/// # Safety
+/// `p` must be the current process, with a valid page table.
+pub unsafe fn syscall_mprotect(p: &mut Proc, addr: u64, len: u64, prot: i32) -> i32 {
+    if !addr.is_multiple_of(PAGESIZE) || len == 0 || !prot_valid(prot)
+        || addr < PROC_START_ADDR || len > MEMSIZE_VIRTUAL - addr {
+        return -1;
+    }
+    let start = addr as usize;
+    let end = start + roundup(len as usize, PAGESIZE as usize);
+    let pid = p.p_pid as usize;
+    if !VMAS[pid].covers(start, end) {
+        return -1;
+    }
+
+    let mut va = start;
+    while va < end {
+        let vam = virtual_memory_lookup(p.p_pagetable, va);
+        if vam.pn >= 0 {
+            let mut flags = prot_flags(prot);
+            if flags.contains(PteFlags::WRITABLE) && pageinfo[vam.pn as usize].refcount > 1 {
+                flags = (flags - PteFlags::WRITABLE) | PteFlags::COW;
+            }
+            protect_range(p.p_pagetable, va, PAGESIZE as usize, flags).unwrap();
+        }
+        va += PAGESIZE as usize;
+    }
+    VMAS[pid].protect(start, end, prot);
+    0
+}
+
+// copy_args_from_user(p, argv)
+//    Copy the NULL-terminated array of strings at `argv` in process `p`.
+//
+//    Returns `None` if the array or one of its strings is unreadable, or
+//    if there are more than ARGV_MAX strings or one is longer than ARG_MAX.
+
+/// # Safety
+/// `p.p_pagetable` must be a valid page table. Allocates from the kernel heap.
+pub unsafe fn copy_args_from_user(p: &Proc, argv: usize) -> Option<Vec<String>> {
+    let mut args = Vec::new();
+    loop {
+        let mut ptr = [0u8; 8];
+        let addr = argv.checked_add(args.len() * ptr.len())?;
+        if !copy_from_user(p, addr, &mut ptr) {
+            return None;
+        }
+        let ptr = u64::from_ne_bytes(ptr) as usize;
+        if ptr == 0 {
+            return Some(args);
+        }
+        if args.len() == ARGV_MAX {
+            return None;
+        }
+        let mut buf = [0u8; ARG_MAX];
+        args.push(String::from(copy_string_from_user(p, ptr, &mut buf)?));
+    }
+}
+
+// syscall_exec(p, name, argv)
+//    Replace the image of process `p` with the program whose name is the
+//    string at `name` (see programs.rs), passing it the NULL-terminated
+//    argument array at `argv`; a null `argv` stands for `{name, NULL}`. The
+//    new image is built in a fresh page table first: its code and data, a
+//    new stack with the arguments, and fresh registers with %rip at the
+//    entry point. Only once it is complete are the old user mappings, mmap
+//    regions and shared memory attachments dropped. The pid stays the
+//    same.
+//
+//    Returns 0 on success, when `p` resumes as the new program. Returns
+//    -1 if the name or arguments are unreadable or too long, the program
+//    is unknown or does not load, or memory runs out; `p` then keeps
+//    running its old image.
+
+/// # Safety
+/// `p` must be the current process. Its old page table is freed on success, so
+/// the caller must not keep pointers into it.
+pub unsafe fn syscall_exec(p: &mut Proc, name: u64, argv: u64) -> i32 {
+    let mut buf = [0u8; PROGRAM_NAME_MAX];
+    let Some(name) = copy_string_from_user(p, name as usize, &mut buf) else {
+        return -1;
+    };
+    let args = if argv == 0 {
+        alloc::vec![String::from(name)]
+    } else {
+        match copy_args_from_user(p, argv as usize) {
+            Some(args) => args,
+            None => return -1,
+        }
+    };
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let pid = p.p_pid as usize;
+    let pagetable = copy_pagetable(pid as i8);
+    if pagetable.is_null() {
+        return -1;
+    }
+
+    let mut image = *p;
+    image.p_pagetable = pagetable;
+    process_init(&mut image, 0);
+    if program_load(&mut image, name) < 0 || !process_stack_init(&mut image, &args) {
+        free_user_memory(pid, pagetable);
+        return -1;
+    }
+
+    shm_exit(pid as PidT, p.p_pagetable);
+    free_user_memory(pid, p.p_pagetable);
+    VMAS[pid].clear();
+    *p = image;
+    0
+}
+
+// syscall_shm_create(p, size)
+//    Create a shared memory segment of `size` bytes for process `p`.
+//
+//    Returns the segment id, or -1 on failure.
+
+/// # Safety
+/// `p` must be the current process.
+pub unsafe fn syscall_shm_create(p: &mut Proc, size: u64) -> i32 {
+    if size == 0 || size > MEMSIZE_VIRTUAL {
+        return -1;
+    }
+    match shm_create(size as usize, p.p_pid) {
+        Some(id) => id as i32,
+        None => -1,
+    }
+}
+
+// syscall_shm_attach(p, id, addr)
+//    Attach shared memory segment `id` to process `p` at `addr`. With
+//    `addr == 0` the kernel picks the address like sys_mmap does.
+//    Otherwise `addr` must be page-aligned and the segment must fit below
+//    the stack's guard page without touching mapped pages or mmap regions.
+//
+//    Returns the address of the segment, or `u64::MAX` ((void *) -1).
+
+/// # Safety
+/// `p` must be the current process, with a valid page table.
+pub unsafe fn syscall_shm_attach(p: &mut Proc, id: i32, addr: u64) -> u64 {
+    if id < 0 || id as usize >= SEGMENTS.len() || !SEGMENTS[id as usize].in_use() {
+        return u64::MAX;
+    }
+    let size = SEGMENTS[id as usize].size();
+    let lo = roundup(p.p_brk as usize, PAGESIZE as usize);
+    let hi = (p.p_stack_limit - PAGESIZE) as usize;
+    let pagetable = p.p_pagetable;
+    let pid = p.p_pid as usize;
+    let busy = |va: usize| virtual_memory_lookup(pagetable, va).pn >= 0;
+
+    let va = if addr == 0 {
+        match VMAS[pid].find_gap(lo, hi, size, busy) {
+            Some(va) => va,
+            None => return u64::MAX,
+        }
+    } else {
+        let va = addr as usize;
+        if !addr.is_multiple_of(PAGESIZE) || addr < PROC_START_ADDR || size > hi.saturating_sub(va)
+            || VMAS[pid].overlaps(va, va + size)
+            || (va..va + size).step_by(PAGESIZE as usize).any(busy) {
+            return u64::MAX;
+        }
+        va
+    };
+
+    if !shm_attach(id as usize, p.p_pid, pagetable, va) {
+        return u64::MAX;
+    }
+    va as u64
+}
+
+// syscall_shm_detach(p, addr)
+//    Detach the shared memory segment attached at `addr` from process `p`.
+//
+//    Returns 0 on success and -1 if no segment is attached there.
+
+/// # Safety
+/// `p` must be the current process, with a valid page table.
+pub unsafe fn syscall_shm_detach(p: &mut Proc, addr: u64) -> i32 {
+    if shm_detach(p.p_pid, p.p_pagetable, addr as usize) { 0 } else { -1 }
+}
+
+// priority_target(p, pid)
+//    The process whose priority `p` may read or change as `pid`: `p`
+//    itself for 0 or its own pid, or one of its children.
+
+unsafe fn priority_target(p: &Proc, pid: i32) -> Option<usize> {
+    if pid == 0 || pid == p.p_pid {
+        return Some(p.p_pid as usize);
+    }
+    let pid = usize::try_from(pid).ok().filter(|&pid| pid < NPROC)?;
+    let child = &processes[pid];
+    (child.p_state != P_FREE && child.p_ppid == p.p_pid).then_some(pid)
+}
+
+// syscall_getpriority(p, pid)
+//    Return the scheduling priority of process `pid` (see
+//    `priority_target`), or -1 if `p` may not see it.
+
+/// # Safety
+/// Reads `processes`; `p` must be the current process.
+pub unsafe fn syscall_getpriority(p: &Proc, pid: i32) -> i32 {
+    match priority_target(p, pid) {
+        Some(pid) => processes[pid].p_priority,
+        None => -1,
+    }
+}
+
+// syscall_setpriority(p, pid, priority)
+//    Set the scheduling priority of process `pid` (see `priority_target`)
+//    to `priority`, between PRIO_MIN and PRIO_MAX. Higher priorities run
+//    more often; what that means exactly is up to the scheduler.
+//
+//    Returns 0 on success and -1 if `p` may not change it or `priority`
+//    is out of range.
+
+/// # Safety
+/// `p` must be the current process, and the scheduler must be initialized: the
+/// change is passed on to it.
+pub unsafe fn syscall_setpriority(p: &mut Proc, pid: i32, priority: i32) -> i32 {
+    if !(PRIO_MIN..=PRIO_MAX).contains(&priority) {
+        return -1;
+    }
+    match priority_target(p, pid) {
+        Some(pid) => {
+            processes[pid].p_priority = priority;
+            scheduler().on_priority(pid);
+            0
+        }
+        None => -1,
+    }
+}
+
+// syscall_ticks(p, pid)
+//    Return the timer interrupts charged to process `pid`, or to `p` if
+//    `pid` is 0. Returns -1 if there is no such process.
+
+/// # Safety
+/// Reads `processes`; `p` must be the current process.
+pub unsafe fn syscall_ticks(p: &Proc, pid: i32) -> i64 {
+    match live_process(p, pid) {
+        Some(pid) => processes[pid].p_ticks as i64,
+        None => -1,
+    }
+}
+
+// syscall_cpu_share(p, pid)
+//    Return the share of the CPU that process `pid` (`p` if `pid` is 0)
+//    got since it started, in thousandths of the timer ticks since then.
+//    Returns -1 if there is no such process.
+
+/// # Safety
+/// Reads `processes` and `ticks`; `p` must be the current process.
+pub unsafe fn syscall_cpu_share(p: &Proc, pid: i32) -> i32 {
+    let Some(pid) = live_process(p, pid) else {
+        return -1;
+    };
+    let elapsed = ticks.wrapping_sub(processes[pid].p_start_ticks) as u64;
+    if elapsed == 0 {
+        return 0;
+    }
+    (processes[pid].p_ticks as u64 * 1000 / elapsed) as i32
+}
+
+// live_process(p, pid)
+//    The slot of process `pid`, or of `p` if `pid` is 0, if it is in use.
+
+unsafe fn live_process(p: &Proc, pid: i32) -> Option<usize> {
+    let pid = if pid == 0 { p.p_pid } else { pid };
+    usize::try_from(pid).ok().filter(|&pid| pid < NPROC && processes[pid].p_state != P_FREE)
+}
+
+// syscall_set_tickets(p, n)
+//    Give process `p` `n` tickets, between 1 and TICKETS_MAX. Returns 0
+//    on success and -1 if `n` is out of range.
+
+/// # Safety
+/// `p` must be the current process, and the scheduler must be initialized: the
+/// change is passed on to it.
+pub unsafe fn syscall_set_tickets(p: &mut Proc, n: u64) -> i32 {
+    if !(1..=TICKETS_MAX as u64).contains(&n) {
+        return -1;
+    }
+    let old = p.p_tickets;
+    p.p_tickets = n as u32;
+    scheduler().on_tickets(p.p_pid as usize, old);
+    0
+}
+
+// handle_vma_fault(p, addr)
+//    Map a zeroed page at the missing page `addr` if it lies in an
+//    accessible mmap region of process `p`.
+//
+//    Returns false if `addr` is in no region, in a PROT_NONE region, or
+//    memory runs out.
+
+/// # Safety
+/// `p` must be the faulting process and `addr` the faulting address.
+pub unsafe fn handle_vma_fault(p: &mut Proc, addr: usize) -> bool {
+    let prot = match VMAS[p.p_pid as usize].find(addr) {
+        Some(vma) if vma.prot != PROT_NONE => vma.prot,
+        _ => return false,
+    };
+    let pa = match FRAMES.allocate(p.p_pid as i8) {
+        Ok(pa) => pa,
+        Err(_) => return false,
+    };
+    core::ptr::write_bytes(pa as *mut u8, 0, PAGESIZE as usize);
+    let va = addr & !(PAGE_OFF_MASK as usize);
+    if virtual_memory_map(p.p_pagetable, va, pa, PAGESIZE as usize,
+                          prot_flags(prot).bits() as i32) < 0 {
+        FRAMES.free(pa).unwrap();
+        return false;
+    }
+    true
 }
 
 // exception(reg)
@@ -149,6 +1080,14 @@ pub unsafe fn process_setup(pid: usize, pn: usize) {
 
 #[no_mangle]
 pub unsafe fn exception(reg: &mut x86_64_registers) {    
+    // A timer interrupt can also arrive while `schedule()` waits for a
+    // process to wake up. It interrupted the kernel, not `current`: count
+    // the tick and return to the waiting loop.
+    if reg.reg_intno == INT_TIMER as u64 && reg.reg_cs & 3 == 0 {
+        timer_tick();
+        exception_return(reg);
+    }
+
     // Copy the saved registers into the `current` process descriptor
     // and always use the kernel's page table.
     (*current).p_registers = *reg;
@@ -179,17 +1118,10 @@ pub unsafe fn exception(reg: &mut x86_64_registers) {
         INT_SYS_PANIC => {
             // rdi stores pointer for msg string
             let addr = (*current).p_registers.reg_rdi;
-            if addr == 0 {
-                panic(core::ptr::null());
-            } else {
-                let map = virtual_memory_lookup((*current).p_pagetable, addr as usize);
-                let mut msg = [0u8; 15];
-                memcpy(
-                    &mut msg as *mut [u8; 15] as *mut core::ffi::c_void, 
-                    map.pa as *const core::ffi::c_void, 
-                    160
-                );
-                c_panic!(msg);
+            let mut buf = [0u8; 160];
+            match copy_string_from_user(&*current, addr as usize, &mut buf) {
+                Some(msg) => c_panic!(msg),
+                None => panic(core::ptr::null()),
             }
             /* will not be reached */
         }
@@ -197,26 +1129,106 @@ pub unsafe fn exception(reg: &mut x86_64_registers) {
             (*current).p_registers.reg_rax = (*current).p_pid as u64;       
         }
         INT_SYS_YIELD => {
+            scheduler().on_yield((*current).p_pid as usize);
             schedule();
             /* will not be reached */
         }
         INT_SYS_PAGE_ALLOC => {
             let addr = (*current).p_registers.reg_rdi;
-            let r = assign_physical_page(
-                addr as usize, 
-                (*current).p_pid as i8,
-            );
-            if r >= 0 {
-                virtual_memory_map(
-                    (*current).p_pagetable, 
-                    addr as usize,
-                    addr as usize,
-                    PAGESIZE as usize,
-                    (PTE_P | PTE_W | PTE_U) as i32,
-                );
-            }
+            (*current).p_registers.reg_rax = syscall_page_alloc(&mut *current, addr) as u64;
+        }
+        INT_SYS_FORK => {
+            (*current).p_registers.reg_rax = syscall_fork(&mut *current) as u64;
+        }
+        INT_SYS_EXIT => {
+            process_free((*current).p_pid as usize);
+            /* `current` is now free, so we schedule another process */
+        }
+        INT_SYS_BRK => {
+            let brk = (*current).p_registers.reg_rdi;
+            let r: i32 = if process_set_brk(&mut *current, brk) { 0 } else { -1 };
             (*current).p_registers.reg_rax = r as u64;
         }
+        INT_SYS_SBRK => {
+            let increment = (*current).p_registers.reg_rdi as i64;
+            let old_brk = (*current).p_brk;
+            let r = match old_brk.checked_add_signed(increment) {
+                Some(brk) if process_set_brk(&mut *current, brk) => old_brk,
+                _ => u64::MAX, // (void *) -1
+            };
+            (*current).p_registers.reg_rax = r;
+        }
+        INT_SYS_MMAP => {
+            let len = (*current).p_registers.reg_rdi;
+            let prot = (*current).p_registers.reg_rsi as i32;
+            (*current).p_registers.reg_rax = syscall_mmap(&mut *current, len, prot);
+        }
+        INT_SYS_MUNMAP => {
+            let addr = (*current).p_registers.reg_rdi;
+            let len = (*current).p_registers.reg_rsi;
+            (*current).p_registers.reg_rax = syscall_munmap(&mut *current, addr, len) as u64;
+        }
+        INT_SYS_MPROTECT => {
+            let addr = (*current).p_registers.reg_rdi;
+            let len = (*current).p_registers.reg_rsi;
+            let prot = (*current).p_registers.reg_rdx as i32;
+            (*current).p_registers.reg_rax =
+                syscall_mprotect(&mut *current, addr, len, prot) as u64;
+        }
+        INT_SYS_EXEC => {
+            let name = (*current).p_registers.reg_rdi;
+            let argv = (*current).p_registers.reg_rsi;
+            // on success the registers belong to the new program
+            if syscall_exec(&mut *current, name, argv) < 0 {
+                (*current).p_registers.reg_rax = -1i64 as u64;
+            }
+        }
+        INT_SYS_GETPRIORITY => {
+            let pid = (*current).p_registers.reg_rdi as i32;
+            (*current).p_registers.reg_rax = syscall_getpriority(&*current, pid) as u64;
+        }
+        INT_SYS_SETPRIORITY => {
+            let pid = (*current).p_registers.reg_rdi as i32;
+            let priority = (*current).p_registers.reg_rsi as i32;
+            (*current).p_registers.reg_rax =
+                syscall_setpriority(&mut *current, pid, priority) as u64;
+        }
+        INT_SYS_TICKS => {
+            let pid = (*current).p_registers.reg_rdi as i32;
+            (*current).p_registers.reg_rax = syscall_ticks(&*current, pid) as u64;
+        }
+        INT_SYS_SET_TICKETS => {
+            let n = (*current).p_registers.reg_rdi as u32 as u64;
+            (*current).p_registers.reg_rax = syscall_set_tickets(&mut *current, n) as u64;
+        }
+        INT_SYS_CPU_SHARE => {
+            let pid = (*current).p_registers.reg_rdi as i32;
+            (*current).p_registers.reg_rax = syscall_cpu_share(&*current, pid) as u64;
+        }
+        INT_SYS_SLEEP => {
+            let n = (*current).p_registers.reg_rdi as u32;
+            if n >= SLEEP_MAX {
+                (*current).p_registers.reg_rax = -1i64 as u64;
+            } else {
+                (*current).p_registers.reg_rax = 0;
+                if n > 0 {
+                    sleep(&mut *current, n);
+                }
+            }
+        }
+        INT_SYS_SHM_CREATE => {
+            let size = (*current).p_registers.reg_rdi;
+            (*current).p_registers.reg_rax = syscall_shm_create(&mut *current, size) as u64;
+        }
+        INT_SYS_SHM_ATTACH => {
+            let id = (*current).p_registers.reg_rdi as i32;
+            let addr = (*current).p_registers.reg_rsi;
+            (*current).p_registers.reg_rax = syscall_shm_attach(&mut *current, id, addr);
+        }
+        INT_SYS_SHM_DETACH => {
+            let addr = (*current).p_registers.reg_rdi;
+            (*current).p_registers.reg_rax = syscall_shm_detach(&mut *current, addr) as u64;
+        }
         INT_SYS_MAPPING => {
             syscall_mapping(&mut *current);
         }
@@ -224,25 +1236,51 @@ pub unsafe fn exception(reg: &mut x86_64_registers) {
             syscall_mem_tog(&mut *current);
         }
         INT_TIMER => {
-            ticks += 1;
-            schedule();
-            /* will not be reached */
+            timer_tick();
+            // the tick is charged to the process it interrupted
+            (*current).p_ticks += 1;
+            if scheduler().on_tick((*current).p_pid as usize) {
+                schedule();
+            }
         }
         INT_PAGEFAULT => {
             // Analyze faulting address and access type.
             let addr = asm_rcr2();
-            let operation = if reg.reg_err & PFERR_WRITE as u64 != 0 { "write" } else { "read" };
+            let operation = if reg.reg_err & PFERR_INSTRUCTION as u64 != 0 {
+                "execute"
+            } else if reg.reg_err & PFERR_WRITE as u64 != 0 {
+                "write"
+            } else {
+                "read"
+            };
             let problem = if reg.reg_err & PFERR_PRESENT as u64 != 0 { "protection problem" } else { "missing page" };
             
             if reg.reg_err & PFERR_USER as u64 == 0 {
                 c_panic!("Kernel page fault for ", addr, " (", operation, " ", problem, ", rip=", reg.reg_rip,")!");
             }
 
-            // TODO
-            console_printf(cpos!(24, 0), 0x0C00, 
-                "Process page fault!\0".as_ptr() as *const u8);
-
-            (*current).p_state = P_BROKEN;
+            // A missing page just below the stack grows the stack, one in
+            // an mmap region is zero-filled, and a write to a present
+            // copy-on-write page is not an error either, the process just
+            // needs its own copy of the page.
+            let cow = (PFERR_WRITE | PFERR_PRESENT) as u64;
+            let handled = if reg.reg_err & PFERR_PRESENT as u64 == 0 {
+                handle_stack_fault(&mut *current, addr as usize)
+                    || handle_vma_fault(&mut *current, addr as usize)
+            } else {
+                reg.reg_err & cow == cow && handle_cow_fault(&mut *current, addr as usize)
+            };
+            if !handled && is_stack_guard(&*current, addr as usize) {
+                c_console!("Process ", (*current).p_pid, " stack overflow at ", addr as *const u8,
+                           " (rip=", reg.reg_rip, ")!");
+                (*current).p_state = P_BROKEN;
+                scheduler().on_block((*current).p_pid as usize);
+            } else if !handled {
+                c_console!("Process ", (*current).p_pid, " page fault for ", addr as *const u8,
+                           " (", operation, " ", problem, ", rip=", reg.reg_rip, ")!");
+                (*current).p_state = P_BROKEN;
+                scheduler().on_block((*current).p_pid as usize);
+            }
         }
         _ => {
             default_exception(&mut *current);
@@ -24,11 +24,8 @@ extern "C-unwind" {
     pub fn pageinfo_init();
     pub fn console_clear();
     pub fn timer_init(hz: u32);
-    pub fn run(p: &mut Proc);
-    pub fn schedule();
     pub fn asm_rcr2() -> u64;
-    pub fn assign_physical_page(addr: usize, owner: i8) -> core::ffi::c_int;
-    pub fn program_load(process: *mut Proc, program_number: i32, arg: *const u8) -> i32;
+    pub fn roundup(a: usize, n: usize) -> usize;
     pub fn process_init(process: *mut Proc, flag: usize);
     pub fn virtual_memory_map(
         pagetable: *mut x86_64_pagetable, // Pointer to the page table
@@ -37,15 +34,11 @@ extern "C-unwind" {
         sz: usize,                        // Size
         perm: i32,                        // Permissions
     ) -> i32;
-    pub fn virtual_memory_lookup(
-        pagetable: *mut x86_64_pagetable, // Pointer to the page table
-        va: usize,                        // Virtual address
-    ) -> VAMapping;
-    pub fn syscall_mapping(p: &mut Proc);
     pub fn syscall_mem_tog(p: &mut Proc);
     pub fn check_keyboard() -> core::ffi::c_int;
     pub fn console_show_cursor(cpos: core::ffi::c_int);
     pub fn set_pagetable(pagetable: *mut x86_64_pagetable);
+    pub fn exception_return(reg: *mut x86_64_registers) -> !;
     pub fn default_exception(p: *mut Proc);
     pub fn memshow_virtual_animate();
     pub fn check_virtual_memory();
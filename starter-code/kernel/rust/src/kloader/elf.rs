@@ -0,0 +1,184 @@
+// elf.rs
+//
+//    Validate ELF executables before they are loaded.
+
+use crate::kloader::*;
+use crate::bindings::bindings_kernel::{MEMSIZE_VIRTUAL, PROC_START_ADDR};
+use crate::aux::traits::ToText;
+use crate::kernel::kernel::STACK_SIZE_MAX;
+
+// ELF IMAGES
+//
+//    A program is an ELF executable in a RAM image, which the kernel does
+//    not trust: `Elf::parse` checks the header and every loadable segment
+//    before anything is mapped, so a broken binary fails to load instead of
+//    making the loader read past the image or map over the kernel.
+//
+//    A valid image is a little-endian, 64-bit x86-64 executable whose
+//    program headers and segment contents lie inside the image. Loadable
+//    segments have `p_filesz <= p_memsz`, sit between PROC_START_ADDR and
+//    the lowest possible stack guard page, and do not share pages with each
+//    other. The entry point lies in an executable segment.
+//
+//    Segment numbers in errors count program headers from 0.
+
+// Highest address a segment may use; the stack and its guard page are
+// above.
+const USER_SEGMENT_END: u64 = MEMSIZE_VIRTUAL - STACK_SIZE_MAX - PAGESIZE;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ElfError {
+    Truncated,                  // the image is smaller than the ELF header
+    BadMagic,                   // not an ELF file
+    BadClass,                   // not a little-endian 64-bit ELF file
+    BadType,                    // not an executable
+    BadMachine,                 // not an x86-64 program
+    BadProgramHeaders,          // program headers are malformed or out of the image
+    SegmentOutOfBounds(usize),  // segment n's contents are out of the image
+    SegmentTooLarge(usize),     // segment n has p_filesz > p_memsz
+    BadAddress(usize),          // segment n is outside of user memory
+    Overlap(usize),             // segment n shares a page with an earlier one
+    BadEntry,                   // the entry point is not in an executable segment
+    OutOfMemory,                // memory ran out while loading
+}
+
+impl ToText for ElfError {
+    fn to_text(&self, buf: &mut [u8]) -> usize {
+        let (what, segment) = match *self {
+            ElfError::Truncated => ("truncated image", None),
+            ElfError::BadMagic => ("not an ELF file", None),
+            ElfError::BadClass => ("not a little-endian 64-bit ELF file", None),
+            ElfError::BadType => ("not an executable", None),
+            ElfError::BadMachine => ("not an x86-64 program", None),
+            ElfError::BadProgramHeaders => ("bad program headers", None),
+            ElfError::SegmentOutOfBounds(n) => ("contents out of the image", Some(n)),
+            ElfError::SegmentTooLarge(n) => ("p_filesz exceeds p_memsz", Some(n)),
+            ElfError::BadAddress(n) => ("address outside user memory", Some(n)),
+            ElfError::Overlap(n) => ("overlaps another segment", Some(n)),
+            ElfError::BadEntry => ("entry point not in an executable segment", None),
+            ElfError::OutOfMemory => ("out of memory", None),
+        };
+        let mut pos = 0;
+        if let Some(n) = segment {
+            pos += "segment ".to_text(buf);
+            pos += n.to_text(&mut buf[pos..]);
+            pos += ": ".to_text(&mut buf[pos..]);
+        }
+        pos + what.to_text(&mut buf[pos..])
+    }
+}
+
+pub struct Elf<'a> {
+    image: &'a [u8],
+    header: ElfHeader,
+}
+
+impl<'a> Elf<'a> {
+    // parse(image)
+    //    Check that `image` is a valid executable (see above).
+    pub fn parse(image: &'a [u8]) -> Result<Self, ElfError> {
+        let header: ElfHeader = read(image, 0).ok_or(ElfError::Truncated)?;
+        if header.e_magic != ELF_MAGIC {
+            return Err(ElfError::BadMagic);
+        }
+        if header.e_elf[0] != ELF_CLASS_64 || header.e_elf[1] != ELF_DATA_LSB {
+            return Err(ElfError::BadClass);
+        }
+        if header.e_type != ELF_ETYPE_EXEC {
+            return Err(ElfError::BadType);
+        }
+        if header.e_machine != ELF_MACHINE_X86_64 {
+            return Err(ElfError::BadMachine);
+        }
+        if header.e_phentsize as usize != core::mem::size_of::<ElfProgram>() {
+            return Err(ElfError::BadProgramHeaders);
+        }
+        let elf = Elf { image, header };
+        for i in 0..header.e_phnum as usize {
+            elf.program(i).ok_or(ElfError::BadProgramHeaders)?;
+        }
+
+        let mut entry_ok = false;
+        for (i, ph) in elf.segments() {
+            let (start, end) = elf.check_segment(i, &ph)?;
+            for (_, other) in elf.segments().take_while(|&(j, _)| j < i) {
+                let (other_start, other_end) = pages(&other);
+                if other_start < end && start < other_end {
+                    return Err(ElfError::Overlap(i));
+                }
+            }
+            let entry = header.e_entry;
+            entry_ok |= ph.p_flags & ELF_PFLAG_EXEC != 0
+                && ph.p_va <= entry && entry - ph.p_va < ph.p_memsz;
+        }
+        if !entry_ok {
+            return Err(ElfError::BadEntry);
+        }
+        Ok(elf)
+    }
+
+    pub fn entry(&self) -> u64 {
+        self.header.e_entry
+    }
+
+    // segments()
+    //    The loadable segments, with their program header numbers.
+    pub fn segments(&self) -> impl Iterator<Item = (usize, ElfProgram)> + '_ {
+        (0..self.header.e_phnum as usize)
+            .filter_map(|i| Some((i, self.program(i)?)))
+            .filter(|(_, ph)| ph.p_type == ELF_PTYPE_LOAD)
+    }
+
+    // contents(ph)
+    //    The bytes of segment `ph` stored in the image (`p_filesz` of them).
+    pub fn contents(&self, ph: &ElfProgram) -> &'a [u8] {
+        let start = ph.p_offset as usize;
+        &self.image[start..start + ph.p_filesz as usize]
+    }
+
+    // program(i)
+    //    Program header number `i`, or `None` if it is out of the image.
+    fn program(&self, i: usize) -> Option<ElfProgram> {
+        let offset = (i * core::mem::size_of::<ElfProgram>())
+            .checked_add(usize::try_from(self.header.e_phoff).ok()?)?;
+        read(self.image, offset)
+    }
+
+    // check_segment(i, ph)
+    //    Check loadable segment number `i` on its own and return the page
+    //    range it occupies.
+    fn check_segment(&self, i: usize, ph: &ElfProgram) -> Result<(u64, u64), ElfError> {
+        let file_end = ph.p_offset.checked_add(ph.p_filesz);
+        if file_end.is_none_or(|end| end > self.image.len() as u64) {
+            return Err(ElfError::SegmentOutOfBounds(i));
+        }
+        if ph.p_filesz > ph.p_memsz {
+            return Err(ElfError::SegmentTooLarge(i));
+        }
+        let mem_end = ph.p_va.checked_add(ph.p_memsz);
+        if ph.p_va < PROC_START_ADDR || mem_end.is_none_or(|end| end > USER_SEGMENT_END) {
+            return Err(ElfError::BadAddress(i));
+        }
+        Ok(pages(ph))
+    }
+}
+
+// pages(ph)
+//    The page-aligned range `[start, end)` the loader maps for `ph`.
+fn pages(ph: &ElfProgram) -> (u64, u64) {
+    let start = ph.p_va & !PAGE_OFF_MASK;
+    let end = (ph.p_va + ph.p_memsz + PAGE_OFF_MASK) & !PAGE_OFF_MASK;
+    (start, end)
+}
+
+// read(image, offset)
+//    Copy a `T` out of `image` at byte `offset`, which need not be aligned.
+//    Returns `None` if it does not fit.
+fn read<T: Copy>(image: &[u8], offset: usize) -> Option<T> {
+    let end = offset.checked_add(core::mem::size_of::<T>())?;
+    if end > image.len() {
+        return None;
+    }
+    // SAFETY: the bytes are in bounds, and ELF structures are plain data
+    Some(unsafe { core::ptr::read_unaligned(image.as_ptr().add(offset) as *const T) })
+}
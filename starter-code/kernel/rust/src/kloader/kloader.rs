@@ -1,49 +1,131 @@
 // k-loader.c
 //
 //    Load a weensy application into memory from a RAM image.
+//    The images are validated first, see elf.rs.
 
 use crate::*;
 use crate::kloader::*;
 use crate::aux::traits::*;
+use crate::frame::frame::FRAMES;
+use crate::vm::pagetable::PteFlags;
+use crate::vm::vm::map_range;
+use crate::kloader::elf::{Elf, ElfError};
+use crate::kloader::programs::find_program;
 
-// program_load_segment(p, ph, src, allocator)
-//    Load an ELF segment at virtual address `ph->p_va` in process `p`. Copies
-//    `[src, src + ph->p_filesz)` to `dst`, then clears
-//    `[ph->p_va + ph->p_filesz, ph->p_va + ph->p_memsz)` to 0.
-//    Calls `assign_physical_page` to allocate pages and `virtual_memory_map`
-//    to map them in `p->p_pagetable`. Returns 0 on success and -1 on failure.
+// segment_flags(ph)
+//    Page table flags for the pages of ELF segment `ph`: user-accessible,
+//    writable only if the segment is (ELF_PFLAG_WRITE), and not executable
+//    unless it is code (ELF_PFLAG_EXEC). Text is thus read-only and data
+//    can never be run (W^X).
+
+pub fn segment_flags(ph: &ElfProgram) -> PteFlags {
+    let mut flags = PteFlags::PRESENT | PteFlags::USER;
+    if ph.p_flags & ELF_PFLAG_WRITE != 0 {
+        flags |= PteFlags::WRITABLE;
+    }
+    if ph.p_flags & ELF_PFLAG_EXEC == 0 {
+        flags |= PteFlags::NO_EXECUTE;
+    }
+    flags
+}
+
+// program_load(p, name)
+//    Load the program called `name` (see programs.rs) into the process
+//    `p` and set `p->p_registers.reg_rip` to its entry point. The program
+//    break starts at the end of the highest loaded segment. The image is
+//    checked by `Elf::parse` before anything is mapped. Returns 0 on
+//    success and -1 on failure (an unknown program, an invalid image, or
+//    out-of-memory); the reason goes to the log. Pages loaded before a
+//    failure stay mapped in `p`, to be freed with it.
+
+/// # Safety
+/// `p.p_pagetable` must be a valid page table owned by `p`.
+pub unsafe fn program_load(p: &mut Proc, name: &str) -> i32 {
+    let Some(program) = find_program(name) else {
+        c_log!("program_load(pid ", p.p_pid, "): no program ", name, "\n");
+        return -1;
+    };
+    match load_elf(p, program.image()) {
+        Ok(()) => 0,
+        Err(e) => {
+            c_log!("program_load(pid ", p.p_pid, "): ", name, ": ", e, "\n");
+            -1
+        }
+    }
+}
+
+// load_elf(p, image)
+//    Load the ELF executable `image` into process `p` as described for
+//    `program_load`.
+
+/// # Safety
+/// `p.p_pagetable` must be a valid page table owned by `p`.
+pub unsafe fn load_elf(p: &mut Proc, image: &[u8]) -> Result<(), ElfError> {
+    let elf = Elf::parse(image)?;
+    let mut brk = 0;
+    for (_, ph) in elf.segments() {
+        program_load_segment(p, &ph, elf.contents(&ph))?;
+        brk = brk.max(ph.p_va + ph.p_memsz);
+    }
+
+    // the heap starts right after the loaded code and data
+    p.p_heap_start = brk;
+    p.p_brk = brk;
+    p.p_registers.reg_rip = elf.entry();
+    Ok(())
+}
+
+// program_load_segment(p, ph, src)
+//    Load an ELF segment at virtual address `ph.p_va` in process `p`. Copies
+//    `src` (the segment's `p_filesz` bytes) to `ph.p_va`, then clears
+//    `[ph.p_va + ph.p_filesz, ph.p_va + ph.p_memsz)` to 0.
+//    Calls `FRAMES.allocate` to allocate any free physical pages and
+//    `map_range` to map them in `p.p_pagetable` with the segment's
+//    permissions (see `segment_flags`). The data is
+//    copied through the kernel's identity mapping of physical memory, so
+//    the process's page table never needs to be loaded. `ph` must have
+//    passed `Elf::parse`.
 
-#[no_mangle]
 pub unsafe fn program_load_segment(
-    p: *mut Proc,
-    ph: *const ElfProgram,
-    src: *const u64,
-) -> i32 {
-    let mut va: u64 = (*ph).p_va;
-    let end_file: u64 = va + (*ph).p_filesz;
-    let end_mem: u64 = va + (*ph).p_memsz;
+    p: &mut Proc,
+    ph: &ElfProgram,
+    src: &[u8],
+) -> Result<(), ElfError> {
+    let mut va: u64 = ph.p_va;
+    let end_file: u64 = va + ph.p_filesz;
+    let end_mem: u64 = va + ph.p_memsz;
     va &= !(PAGESIZE - 1); // round to page boundary
+    let flags = segment_flags(ph);
 
-    // allocate memory
     let mut addr = va;
     while addr < end_mem {
-        if assign_physical_page(addr as usize, (*p).p_pid as i8) < 0 ||
-            virtual_memory_map((*p).p_pagetable, addr as usize, addr as usize, PAGESIZE as usize, 
-                                (PTE_U | PTE_W | PTE_P) as i32) < 0 {
-            c_console!("program_load_segment(pid %d): can't assign address %p", (*p).p_pid, addr);
-            return -1;
+        // allocate memory
+        let pa = match FRAMES.allocate(p.p_pid as i8) {
+            Ok(pa) => pa,
+            Err(_) => {
+                c_console!("program_load_segment(pid ", p.p_pid, "): can't allocate page for ", addr as *const u8);
+                return Err(ElfError::OutOfMemory);
+            }
+        };
+        if map_range(p.p_pagetable, addr as usize, pa, PAGESIZE as usize, flags).is_err() {
+            c_console!("program_load_segment(pid ", p.p_pid, "): can't map address ", addr as *const u8);
+            FRAMES.free(pa).unwrap();
+            return Err(ElfError::OutOfMemory);
+        }
+
+        // copy the part of the executable image that falls into this page,
+        // the rest of the page is zero
+        core::ptr::write_bytes(pa as *mut u8, 0, PAGESIZE as usize);
+        let start = addr.max(ph.p_va);
+        let end = (addr + PAGESIZE).min(end_file);
+        if start < end {
+            let from = &src[(start - ph.p_va) as usize..(end - ph.p_va) as usize];
+            from.as_ptr().copy_to_nonoverlapping(
+                (pa as *mut u8).add((start - addr) as usize),
+                from.len(),
+            );
         }
         addr += PAGESIZE;
     }
-
-    // ensure new memory mappings are active
-    set_pagetable((*p).p_pagetable);
-    
-    // copy data from executable image into process memory
-    (va as *mut u8).copy_from_nonoverlapping(src as *const u8, (end_file - va) as usize);
-    core::ptr::write_bytes(end_file as *mut u8, 0, (end_mem - end_file) as usize);
-
-    // restore the kernel pagetable
-    set_pagetable(kernel_pagetable);
-    0 // success
+    Ok(())
 }
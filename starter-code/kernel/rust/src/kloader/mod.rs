@@ -3,6 +3,8 @@
 // https://doc.rust-lang.org/nomicon/ffi.html#foreign-calling-conventions
 
 pub mod kloader;
+pub mod elf;
+pub mod programs;
 
 use crate::bindings::bindings_elf::*;
 use crate::bindings::bindings_x86_64::*;
@@ -11,14 +13,21 @@ extern "C-unwind" {
     pub static kernel_pagetable: *mut x86_64_pagetable;
 }
 
+// Program images linked into the kernel, see programs.rs.
+extern "C-unwind" {
+    pub static _binary_obj_p_allocator_start: u8;
+    pub static _binary_obj_p_allocator_end: u8;
+    pub static _binary_obj_p_fork_start: u8;
+    pub static _binary_obj_p_fork_end: u8;
+    pub static _binary_obj_p_forkexit_start: u8;
+    pub static _binary_obj_p_forkexit_end: u8;
+    pub static _binary_obj_p_test_start: u8;
+    pub static _binary_obj_p_test_end: u8;
+}
+
 extern "C-unwind" {
     pub fn set_pagetable(pagetable: *mut x86_64_pagetable);
-    pub fn assign_physical_page(addr: usize, owner: i8) -> core::ffi::c_int;
     pub fn roundup(a: usize, n: usize) -> usize;
-    pub fn virtual_memory_lookup(
-        pagetable: *mut x86_64_pagetable, // Pointer to the page table
-        va: usize,                        // Virtual address
-    ) -> VAMapping;
     pub fn virtual_memory_map(
         pagetable: *mut x86_64_pagetable, // Pointer to the page table
         va: usize,                        // Virtual address
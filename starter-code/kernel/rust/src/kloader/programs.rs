@@ -0,0 +1,58 @@
+// programs.rs
+//
+//    The programs linked into the kernel, by name.
+
+use crate::kloader::*;
+use core::ptr::addr_of;
+
+// PROGRAM REGISTRY
+//
+//    Every application is linked into the kernel image as a binary blob
+//    (see PROCESS_BINARIES in the GNUmakefile), which the linker brackets
+//    with `_binary_obj_p_NAME_start` and `_binary_obj_p_NAME_end`. The
+//    registry gives each blob the name of its source file without the
+//    `p-` prefix, so boot commands and sys_exec can refer to programs by
+//    name.
+//
+//    To add a program, add it to PROCESS_BINARIES and PROCESS_OBJS, declare
+//    its two symbols in kloader/mod.rs, and list it in `PROGRAMS`.
+
+pub struct Program {
+    pub name: &'static str,
+    start: *const u8,
+    end: *const u8,
+}
+
+// The registry never changes and the images are read-only.
+unsafe impl Sync for Program {}
+
+impl Program {
+    // image()
+    //    The program's ELF image.
+    pub fn image(&self) -> &'static [u8] {
+        // SAFETY: the linker places the whole image between the symbols
+        unsafe {
+            core::slice::from_raw_parts(self.start, self.end.offset_from(self.start) as usize)
+        }
+    }
+}
+
+macro_rules! program {
+    ($name:expr, $start:ident, $end:ident) => {
+        Program { name: $name, start: addr_of!($start), end: addr_of!($end) }
+    };
+}
+
+pub static PROGRAMS: [Program; 4] = [
+    program!("allocator", _binary_obj_p_allocator_start, _binary_obj_p_allocator_end),
+    program!("fork", _binary_obj_p_fork_start, _binary_obj_p_fork_end),
+    program!("forkexit", _binary_obj_p_forkexit_start, _binary_obj_p_forkexit_end),
+    program!("test", _binary_obj_p_test_start, _binary_obj_p_test_end),
+];
+
+// find_program(name)
+//    Return the program called `name`, if there is one.
+
+pub fn find_program(name: &str) -> Option<&'static Program> {
+    PROGRAMS.iter().find(|program| program.name == name)
+}
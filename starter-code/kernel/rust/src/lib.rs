@@ -7,9 +7,13 @@
 // Create an "baremetal" executable that
 // can be run without an underlying OS.
 #![allow(static_mut_refs)]
+#![feature(alloc_error_handler)]
 #![no_main]
 #![no_std]
 
+// Box, Vec and String backed by the kernel heap (see heap/heap.rs).
+extern crate alloc;
+
 pub mod aux;
 // C headers has been translated to a Rust files
 // using rust-bindgen tool that allows assert that
@@ -22,7 +26,10 @@ pub mod bindings;
 // to link with C-base in toml.
 pub mod kloader;
 pub mod kernel;
+pub mod frame;
+pub mod heap;
 pub mod vm;
+pub mod sched;
 
 // Rust has a minimal runtime that handles tasks such as setting up 
 // stack overflow guards and printing a backtrace on panic. Writing an 
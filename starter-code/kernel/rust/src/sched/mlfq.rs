@@ -0,0 +1,104 @@
+// mlfq.rs
+//
+//    Multi-level feedback queue scheduling.
+
+use crate::sched::*;
+use crate::sched::sched::Scheduler;
+
+// MULTI-LEVEL FEEDBACK QUEUE
+//
+//    Every process sits on one of NLEVELS levels, and the runnable
+//    processes on the highest level (level 0) run first, taking turns in
+//    pid order. Processes start on level 0. Each level has a quantum of
+//    timer ticks: a process that runs for its whole quantum is CPU-bound
+//    and moves down a level, while one that calls sys_yield or blocks
+//    before then starts a new quantum on the same level. A process is also
+//    preempted as soon as a process on a higher level is runnable.
+//
+//    Every BOOST_TICKS ticks all processes move back to level 0, so that
+//    CPU-bound processes are not starved by interactive ones, and so that a
+//    process whose behavior changes gets a new chance.
+//
+//    A process that blocks keeps its level; one that exits or faults
+//    leaves the top level to the next process in its slot.
+//
+//    Priorities set with sys_setpriority play no part.
+
+const NLEVELS: usize = 3;
+const QUANTUM: [u32; NLEVELS] = [1, 2, 4];   // ticks per turn on each level
+const BOOST_TICKS: u32 = 100;                // one second at HZ = 100
+
+pub struct Mlfq {
+    level: [usize; NPROC],      // level of process `pid`
+    used: [u32; NPROC],         // ticks used of its current quantum
+    since_boost: u32,           // ticks since everyone was moved to level 0
+}
+
+impl Default for Mlfq {
+    fn default() -> Self {
+        Mlfq::new()
+    }
+}
+
+impl Mlfq {
+    pub const fn new() -> Self {
+        Mlfq { level: [0; NPROC], used: [0; NPROC], since_boost: 0 }
+    }
+
+    // boost()
+    //    Move every process back to level 0 with a fresh quantum.
+    fn boost(&mut self) {
+        self.level = [0; NPROC];
+        self.used = [0; NPROC];
+        self.since_boost = 0;
+    }
+
+    // highest_runnable()
+    //    The highest level with a runnable process on it.
+    fn highest_runnable(&self) -> Option<usize> {
+        (0..NPROC)
+            .filter(|&pid| unsafe { processes[pid].p_state } == P_RUNNABLE)
+            .map(|pid| self.level[pid])
+            .min()
+    }
+}
+
+impl Scheduler for Mlfq {
+    fn pick_next(&mut self, last: usize) -> Option<usize> {
+        let level = self.highest_runnable()?;
+        (1..=NPROC)
+            .map(|i| (last + i) % NPROC)
+            .find(|&pid| unsafe { processes[pid].p_state } == P_RUNNABLE && self.level[pid] == level)
+    }
+
+    fn on_tick(&mut self, pid: usize) -> bool {
+        self.since_boost += 1;
+        if self.since_boost >= BOOST_TICKS {
+            self.boost();
+            return true;
+        }
+
+        self.used[pid] += 1;
+        if self.used[pid] >= QUANTUM[self.level[pid]] {
+            self.level[pid] = (self.level[pid] + 1).min(NLEVELS - 1);
+            self.used[pid] = 0;
+            return true;
+        }
+        self.highest_runnable().is_some_and(|level| level < self.level[pid])
+    }
+
+    fn on_block(&mut self, pid: usize) {
+        if unsafe { processes[pid].p_state } != P_BLOCKED {
+            self.level[pid] = 0;
+        }
+        self.used[pid] = 0;
+    }
+
+    fn on_wake(&mut self, pid: usize) {
+        self.used[pid] = 0;
+    }
+
+    fn on_yield(&mut self, pid: usize) {
+        self.used[pid] = 0;
+    }
+}
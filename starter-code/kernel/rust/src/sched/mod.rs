@@ -0,0 +1,26 @@
+// This file is for linking C scheduling functionality with Rust.
+// For better understanding of FFI consider reading this documentation.
+// https://doc.rust-lang.org/nomicon/ffi.html#foreign-calling-conventions
+
+#[allow(clippy::module_inception)]
+pub mod sched;
+pub mod rr;
+pub mod priority;
+pub mod mlfq;
+pub mod stride;
+pub mod timer;
+
+use crate::bindings::bindings_x86_64::*;
+use crate::bindings::bindings_kernel::*;
+
+extern "C-unwind" {
+    pub static mut ticks: u32;
+    pub static mut current: *mut Proc;
+    pub static mut processes: [Proc; NPROC];
+}
+
+extern "C-unwind" {
+    pub fn set_pagetable(pagetable: *mut x86_64_pagetable);
+    pub fn exception_return(reg: *mut x86_64_registers) -> !;
+    pub fn check_keyboard() -> core::ffi::c_int;
+}
@@ -0,0 +1,85 @@
+// priority.rs
+//
+//    Priority scheduling with aging.
+
+use crate::sched::*;
+use crate::sched::sched::Scheduler;
+
+// PRIORITY SCHEDULING
+//
+//    The runnable process with the highest priority runs next, and among
+//    equals they take turns in pid order as in round robin. Priorities are
+//    set with sys_setpriority (see `p_priority`).
+//
+//    So that low-priority processes do not starve, a runnable process that
+//    is passed over ages: its effective priority rises by one every
+//    decision until it runs, which resets it to its own priority. A
+//    process PRIO_MAX - PRIO_MIN levels below the best one therefore still
+//    runs about once every PRIO_MAX - PRIO_MIN decisions. Changing a
+//    process's priority restarts its aging.
+//
+//    Every timer interrupt ends the turn of the running process.
+
+pub struct PriorityScheduler {
+    passed_over: [u32; NPROC],   // decisions since process `pid` last ran
+}
+
+impl Default for PriorityScheduler {
+    fn default() -> Self {
+        PriorityScheduler::new()
+    }
+}
+
+impl PriorityScheduler {
+    pub const fn new() -> Self {
+        PriorityScheduler { passed_over: [0; NPROC] }
+    }
+
+    // effective_priority(pid)
+    //    The priority of process `pid`, raised by aging.
+    unsafe fn effective_priority(&self, pid: usize) -> u32 {
+        let priority = processes[pid].p_priority.clamp(PRIO_MIN, PRIO_MAX) as u32;
+        priority.saturating_add(self.passed_over[pid])
+    }
+}
+
+impl Scheduler for PriorityScheduler {
+    fn pick_next(&mut self, last: usize) -> Option<usize> {
+        let mut best: Option<(usize, u32)> = None;
+        for pid in (1..=NPROC).map(|i| (last + i) % NPROC) {
+            if unsafe { processes[pid].p_state } != P_RUNNABLE {
+                continue;
+            }
+            let priority = unsafe { self.effective_priority(pid) };
+            // the first of equals after `last` wins, as in round robin
+            if best.is_none_or(|(_, best_priority)| priority > best_priority) {
+                best = Some((pid, priority));
+            }
+        }
+
+        let (next, _) = best?;
+        for (pid, passed_over) in self.passed_over.iter_mut().enumerate() {
+            if pid != next && unsafe { processes[pid].p_state } == P_RUNNABLE {
+                *passed_over = passed_over.saturating_add(1);
+            }
+        }
+        self.passed_over[next] = 0;
+        Some(next)
+    }
+
+    fn on_tick(&mut self, _pid: usize) -> bool {
+        true
+    }
+
+    fn on_block(&mut self, pid: usize) {
+        self.passed_over[pid] = 0;
+    }
+
+    fn on_wake(&mut self, pid: usize) {
+        self.passed_over[pid] = 0;
+    }
+
+    fn on_priority(&mut self, pid: usize) {
+        self.passed_over[pid] = 0;
+    }
+}
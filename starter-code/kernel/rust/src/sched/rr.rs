@@ -0,0 +1,29 @@
+// rr.rs
+//
+//    Round-robin scheduling.
+
+use crate::sched::*;
+use crate::sched::sched::Scheduler;
+
+// ROUND ROBIN
+//
+//    Runnable processes take turns in pid order, and every timer interrupt
+//    ends the turn of the running process. This is the default policy.
+
+pub struct RoundRobin;
+
+impl Scheduler for RoundRobin {
+    fn pick_next(&mut self, last: usize) -> Option<usize> {
+        (1..=NPROC)
+            .map(|i| (last + i) % NPROC)
+            .find(|&pid| unsafe { processes[pid].p_state } == P_RUNNABLE)
+    }
+
+    fn on_tick(&mut self, _pid: usize) -> bool {
+        true
+    }
+
+    fn on_block(&mut self, _pid: usize) {}
+
+    fn on_wake(&mut self, _pid: usize) {}
+}
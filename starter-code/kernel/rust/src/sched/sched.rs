@@ -0,0 +1,163 @@
+// sched.rs
+//
+//    Choosing which process runs next.
+
+use crate::*;
+use crate::sched::*;
+use crate::sched::rr::RoundRobin;
+use crate::sched::priority::PriorityScheduler;
+use crate::sched::mlfq::Mlfq;
+use crate::sched::stride::Stride;
+use crate::sched::timer::{TimerWheel, TIMERS};
+use crate::aux::traits::ToText;
+use alloc::boxed::Box;
+
+// SCHEDULER
+//
+//    The kernel asks the scheduler which process to run whenever the
+//    current one gives up the CPU: on sys_yield, on exit, when it faults,
+//    and when a timer interrupt preempts it. The policy behind it is a
+//    `Scheduler`, picked once per boot (see `sched_init`), so policies can
+//    be swapped without touching `exception()`.
+//
+//    The kernel tells the policy about processes that become runnable
+//    (new, forked or woken up), that stop being runnable (blocked, exited
+//    or broken), and that yield, and about priority and ticket changes.
+//    When a process stops being runnable its `p_state` is already
+//    P_BLOCKED or P_BROKEN, or still P_RUNNABLE if it exits. A policy may
+//    keep its own state about them, but `processes[pid].p_state` stays
+//    authoritative: `pick_next` must only return P_RUNNABLE processes.
+
+pub trait Scheduler {
+    // pick_next(last)
+    //    Return the pid of the next process to run, or `None` if no process
+    //    is runnable. `last` is the pid of the process that ran last, which
+    //    may itself be picked again.
+    fn pick_next(&mut self, last: usize) -> Option<usize>;
+
+    // on_tick(pid)
+    //    A timer interrupt arrived while process `pid` was running. Return
+    //    true if it should give up the CPU.
+    fn on_tick(&mut self, pid: usize) -> bool;
+
+    // on_block(pid)
+    //    Process `pid` stopped being runnable.
+    fn on_block(&mut self, pid: usize);
+
+    // on_wake(pid)
+    //    Process `pid` became runnable.
+    fn on_wake(&mut self, pid: usize);
+
+    // on_yield(pid)
+    //    Process `pid` gave up the CPU with sys_yield; it stays runnable.
+    fn on_yield(&mut self, _pid: usize) {}
+
+    // on_priority(pid)
+    //    The priority of process `pid` changed (see sys_setpriority).
+    fn on_priority(&mut self, _pid: usize) {}
+
+    // on_tickets(pid, old)
+    //    Process `pid` held `old` tickets and now holds `p_tickets` (see
+    //    sys_set_tickets).
+    fn on_tickets(&mut self, _pid: usize, _old: u32) {}
+}
+
+// POLICIES
+//
+//    A boot command starting with `sched=NAME` selects policy NAME; the
+//    rest of the command is the usual boot command. Without one, or with
+//    an unknown name, processes are scheduled round-robin.
+
+// Makes a fresh instance of a policy.
+type PolicyInit = fn() -> Box<dyn Scheduler>;
+
+const POLICIES: &[(&str, PolicyInit)] = &[
+    ("rr", || Box::new(RoundRobin)),
+    ("prio", || Box::new(PriorityScheduler::new())),
+    ("mlfq", || Box::new(Mlfq::new())),
+    ("stride", || Box::new(Stride::new())),
+];
+
+static mut SCHEDULER: Option<Box<dyn Scheduler>> = None;
+
+// sched_init(command)
+//    Select the scheduling policy from boot command `command` and return
+//    what is left of the command, and clear the timer wheel. Must run
+//    after heap_init().
+
+/// # Safety
+/// The heap must be initialized. The previous scheduler is leaked, not dropped,
+/// so nothing may still refer to it.
+pub unsafe fn sched_init(mut command: Option<&str>) -> Option<&str> {
+    let mut policy = &POLICIES[0];
+    if let Some(option) = command.and_then(|c| c.trim_start().strip_prefix("sched=")) {
+        let (name, rest) = option.split_once(' ').unwrap_or((option, ""));
+        match POLICIES.iter().find(|&&(n, _)| n == name) {
+            Some(named) => policy = named,
+            None => c_console!("Unknown scheduler ", name, ", using rr!"),
+        }
+        command = Some(rest.trim_start()).filter(|rest| !rest.is_empty());
+    }
+    // A soft reboot reinitializes the heap under the previous scheduler,
+    // which must not be dropped.
+    core::ptr::addr_of_mut!(SCHEDULER).write(Some((policy.1)()));
+    TIMERS = TimerWheel::new();
+    command
+}
+
+// scheduler()
+//    The scheduling policy selected at boot.
+
+/// # Safety
+/// `sched_init` must have run. The returned reference must not be held across
+/// another call to `scheduler`.
+pub unsafe fn scheduler() -> &'static mut dyn Scheduler {
+    match SCHEDULER.as_deref_mut() {
+        Some(scheduler) => scheduler,
+        None => c_panic!("Scheduler used before sched_init!"),
+    }
+}
+
+// schedule()
+//    Pick the next process to run and then run it.
+//    If there are no runnable processes, waits for the next timer
+//    interrupt and tries again, forever. A process that is asleep until
+//    then does not run in between (see `exception()`).
+
+/// # Safety
+/// Must be called from the kernel with interrupts disabled. Never returns, so
+/// no kernel state may be left half-updated.
+pub unsafe fn schedule() -> ! {
+    let last = (*current).p_pid as usize;
+    loop {
+        if let Some(pid) = scheduler().pick_next(last) {
+            run(&mut processes[pid]);
+        }
+        // If Control-C was typed, exit the virtual machine.
+        check_keyboard();
+        // The kernel runs with interrupts disabled, except here.
+        x86_64::instructions::interrupts::enable_and_hlt();
+        x86_64::instructions::interrupts::disable();
+    }
+}
+
+// run(p)
+//    Run process `p`. This means reloading all the registers from
+//    `p.p_registers` and returning to user mode with `iretq`.
+//
+//    As a side effect, sets `current = p`.
+
+/// # Safety
+/// `p` must be a runnable process with a valid page table and registers. Never
+/// returns.
+pub unsafe fn run(p: &mut Proc) -> ! {
+    assert!(p.p_state == P_RUNNABLE);
+    current = p;
+
+    // Load the process's current pagetable.
+    set_pagetable(p.p_pagetable);
+
+    // This function is defined in k-exception.S. It restores the process's
+    // registers then jumps back to user mode.
+    exception_return(&mut p.p_registers);
+}
@@ -0,0 +1,88 @@
+// stride.rs
+//
+//    Stride scheduling: proportional shares of the CPU.
+
+use crate::sched::*;
+use crate::sched::sched::Scheduler;
+
+// STRIDE SCHEDULING
+//
+//    Each process holds tickets (see `p_tickets` and sys_set_tickets), and
+//    runnable processes share the CPU in proportion to them. Every process
+//    has a pass value, and the runnable process with the lowest pass runs
+//    next; equal passes take turns in pid order. Each timer tick a process
+//    is charged for advances its pass by its stride, STRIDE1 / tickets, so
+//    a process with twice the tickets is charged half as much per tick and
+//    runs twice as often. Time is only charged by the tick, so a process
+//    that yields before the next tick may well be picked again.
+//
+//    A process that becomes runnable starts at the lowest pass of the
+//    others, neither catching up on time it did not compete for nor losing
+//    its place if it is already behind. When a process's tickets change,
+//    any lead it has over the lowest pass was built up at its old stride
+//    and is rescaled to the new one.
+
+const STRIDE1: u64 = 1 << 20;
+
+pub struct Stride {
+    pass: [u64; NPROC],     // virtual time of process `pid`
+}
+
+impl Default for Stride {
+    fn default() -> Self {
+        Stride::new()
+    }
+}
+
+impl Stride {
+    pub const fn new() -> Self {
+        Stride { pass: [0; NPROC] }
+    }
+
+    // min_pass(except)
+    //    The lowest pass among runnable processes other than `except`.
+    fn min_pass(&self, except: usize) -> Option<u64> {
+        (0..NPROC)
+            .filter(|&pid| pid != except && unsafe { processes[pid].p_state } == P_RUNNABLE)
+            .map(|pid| self.pass[pid])
+            .min()
+    }
+}
+
+impl Scheduler for Stride {
+    fn pick_next(&mut self, last: usize) -> Option<usize> {
+        (1..=NPROC)
+            .map(|i| (last + i) % NPROC)
+            .filter(|&pid| unsafe { processes[pid].p_state } == P_RUNNABLE)
+            .min_by_key(|&pid| self.pass[pid])
+    }
+
+    fn on_tick(&mut self, pid: usize) -> bool {
+        let tickets = unsafe { processes[pid].p_tickets }.clamp(1, TICKETS_MAX);
+        self.pass[pid] += STRIDE1 / tickets as u64;
+        true
+    }
+
+    fn on_block(&mut self, pid: usize) {
+        // a process that exits or faults leaves its slot without a pass
+        if unsafe { processes[pid].p_state } != P_BLOCKED {
+            self.pass[pid] = 0;
+        }
+    }
+
+    fn on_wake(&mut self, pid: usize) {
+        if let Some(min) = self.min_pass(pid) {
+            self.pass[pid] = self.pass[pid].max(min);
+        }
+    }
+
+    fn on_tickets(&mut self, pid: usize, old: u32) {
+        let old = old.clamp(1, TICKETS_MAX) as u64;
+        let new = unsafe { processes[pid].p_tickets }.clamp(1, TICKETS_MAX) as u64;
+        if let Some(min) = self.min_pass(pid) {
+            if self.pass[pid] > min {
+                self.pass[pid] = min + (self.pass[pid] - min) * old / new;
+            }
+        }
+    }
+}
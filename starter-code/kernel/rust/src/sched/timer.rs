@@ -0,0 +1,94 @@
+// timer.rs
+//
+//    Sleeping processes and the timer wheel that wakes them.
+
+use crate::sched::*;
+use crate::sched::sched::scheduler;
+
+// TIMER WHEEL
+//
+//    A process in sys_sleep is P_BLOCKED until `ticks` reaches its
+//    deadline. Its pid goes into the wheel slot `deadline % WHEEL_SLOTS`,
+//    a bitmask of pids, so every timer tick only looks at the processes in
+//    one slot. Deadlines more than WHEEL_SLOTS ticks away stay in their
+//    slot for another turn of the wheel.
+//
+//    Deadlines are compared with wrapping arithmetic, so a sleep must be
+//    shorter than SLEEP_MAX ticks.
+
+const WHEEL_SLOTS: usize = 64;
+pub const SLEEP_MAX: u32 = i32::MAX as u32;
+
+pub struct TimerWheel {
+    slots: [u32; WHEEL_SLOTS],      // bit `pid` set iff process `pid` sleeps in this slot
+    deadline: [u32; NPROC],         // tick at which process `pid` wakes up
+}
+
+pub static mut TIMERS: TimerWheel = TimerWheel::new();
+
+impl Default for TimerWheel {
+    fn default() -> Self {
+        TimerWheel::new()
+    }
+}
+
+impl TimerWheel {
+    pub const fn new() -> Self {
+        TimerWheel { slots: [0; WHEEL_SLOTS], deadline: [0; NPROC] }
+    }
+
+    // add(pid, deadline)
+    //    Wake process `pid` at tick `deadline`.
+    pub fn add(&mut self, pid: usize, deadline: u32) {
+        self.deadline[pid] = deadline;
+        self.slots[deadline as usize % WHEEL_SLOTS] |= 1 << pid;
+    }
+
+    // cancel(pid)
+    //    Forget the deadline of process `pid`, if it has one.
+    pub fn cancel(&mut self, pid: usize) {
+        self.slots[self.deadline[pid] as usize % WHEEL_SLOTS] &= !(1 << pid);
+    }
+
+    // expire(now)
+    //    Remove the processes whose deadline is tick `now` or earlier from
+    //    the slot of `now`, and return them as a bitmask of pids.
+    pub fn expire(&mut self, now: u32) -> u32 {
+        let slot = &mut self.slots[now as usize % WHEEL_SLOTS];
+        let mut expired = 0;
+        for pid in 0..NPROC {
+            if *slot & (1 << pid) != 0 && now.wrapping_sub(self.deadline[pid]) as i32 >= 0 {
+                expired |= 1 << pid;
+            }
+        }
+        *slot &= !expired;
+        expired
+    }
+}
+
+// sleep(p, n)
+//    Block process `p` for `n` timer ticks, `0 < n < SLEEP_MAX`. The
+//    caller must then schedule another process.
+
+/// # Safety
+/// `p` must be the current process, and the scheduler must be initialized.
+pub unsafe fn sleep(p: &mut Proc, n: u32) {
+    let pid = p.p_pid as usize;
+    p.p_state = P_BLOCKED;
+    scheduler().on_block(pid);
+    TIMERS.add(pid, ticks.wrapping_add(n));
+}
+
+// timer_tick()
+//    Count a timer interrupt and wake the processes whose sleep is over.
+
+/// # Safety
+/// Called once per timer interrupt, with interrupts disabled.
+pub unsafe fn timer_tick() {
+    ticks = ticks.wrapping_add(1);
+    let expired = TIMERS.expire(ticks);
+    for pid in (0..NPROC).filter(|&pid| expired & (1 << pid) != 0) {
+        processes[pid].p_state = P_RUNNABLE;
+        scheduler().on_wake(pid);
+    }
+}
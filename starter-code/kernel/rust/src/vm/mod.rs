@@ -3,12 +3,18 @@
 // https://doc.rust-lang.org/nomicon/ffi.html#foreign-calling-conventions
 
 pub mod vm;
+pub mod pagetable;
+pub mod vma;
+pub mod shm;
 
+use crate::kernel::kernel::PhysicalPageInfo;
 use crate::bindings::bindings_x86_64::*;
 use crate::bindings::bindings_kernel::*;
 
 extern "C-unwind" {
     pub static kernel_pagetable: *mut x86_64_pagetable;
+    pub static mut pageinfo: [PhysicalPageInfo; NPAGES as usize];
+    pub static mut console: [u16; CONSOLE_ROWS * CONSOLE_COLUMNS];
 }
 
 extern "C-unwind" {
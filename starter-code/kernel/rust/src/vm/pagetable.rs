@@ -0,0 +1,550 @@
+// pagetable.rs
+//
+//    Typed access to x86-64 page tables.
+
+use crate::vm::*;
+use crate::vm::vm::{PTE_COW, PTE_SHM};
+use crate::aux::traits::ToText;
+use bitflags::bitflags;
+use crate::frame::frame::FRAMES;
+use crate::kernel::kernel::PageOwner;
+use core::ptr::NonNull;
+
+// Bytes mapped by an L2 entry with PTE_PS.
+pub const LARGE_PAGESIZE: u64 = PAGESIZE << PAGEINDEXBITS;
+
+// PAGE TABLE API
+//
+//    `PageTable` wraps the L4 page table of a four-level hierarchy and
+//    walks it on behalf of the rest of the kernel. Addresses are typed
+//    (`VirtAddr`, `PhysAddr`) and entries are read and written as
+//    `PageTableEntry` values with `PteFlags` permissions, so callers never
+//    do their own shifting and masking.
+//
+//    Lookups report a missing level as `PageTableError::MissingTable`.
+//    Mapping a page allocates the missing lower page tables on the way,
+//    owned like the L4 page table (see `check_page_table_ownership`), and
+//    `unmap_range` frees them again once they are empty. Page table pages
+//    are reached through the kernel's identity mapping of physical memory.
+//    Every changed entry is flushed from the TLB.
+//
+//    LARGE PAGES
+//
+//    The kernel identity-maps physical memory with 2 MB pages (PTE_PS in
+//    an L2 entry, see `map_large`). Large pages are for kernel-only,
+//    physically contiguous ranges; user memory always uses 4 KB pages. When
+//    a 4 KB entry inside a large page is changed, `set` first splits the
+//    large page into an L1 page table with the same mappings.
+
+bitflags! {
+    // Page table entry flags, see `PTE_*` in x86-64.h.
+    #[derive(Debug, Copy, Clone, PartialEq, Eq)]
+    pub struct PteFlags: X86_64PageentryT {
+        const PRESENT = PTE_P;
+        const WRITABLE = PTE_W;
+        const USER = PTE_U;
+        const ACCESSED = PTE_A;
+        const DIRTY = PTE_D;
+        const HUGE = PTE_PS;
+        const COW = PTE_COW;
+        const SHM = PTE_SHM;
+        const NO_EXECUTE = PTE_NX;
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum PageTableError {
+    Misaligned,             // address is not page-aligned
+    OutOfRange,             // physical address is beyond MEMSIZE_PHYSICAL
+    NotMapped,              // no page is mapped at the virtual address
+    MissingTable(usize),    // the Ln page table (n = 3, 2 or 1) is absent
+    HugePage(usize),        // an Ln entry (n = 3 or 2) maps a large page
+    TablePresent,           // a large page would replace an L1 page table
+    OutOfMemory,            // no page left to split a large page
+    PermissionDenied(usize), // an entry of the Ln page table lacks the requested flags
+}
+
+impl ToText for PageTableError {
+    fn to_text(&self, buf: &mut [u8]) -> usize {
+        match *self {
+            PageTableError::Misaligned => "misaligned address".to_text(buf),
+            PageTableError::OutOfRange => "address out of range".to_text(buf),
+            PageTableError::NotMapped => "not mapped".to_text(buf),
+            PageTableError::MissingTable(level) => {
+                let mut pos = "no L".to_text(buf);
+                pos += level.to_text(&mut buf[pos..]);
+                pos + " page table".to_text(&mut buf[pos..])
+            }
+            PageTableError::HugePage(level) => {
+                let pos = "large page at L".to_text(buf);
+                pos + level.to_text(&mut buf[pos..])
+            }
+            PageTableError::TablePresent => "L1 page table in the way".to_text(buf),
+            PageTableError::OutOfMemory => "out of memory".to_text(buf),
+            PageTableError::PermissionDenied(level) => {
+                let pos = "permission denied at L".to_text(buf);
+                pos + level.to_text(&mut buf[pos..])
+            }
+        }
+    }
+}
+
+#[repr(transparent)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct PhysAddr(u64);
+
+impl PhysAddr {
+    pub const fn new(addr: u64) -> Self {
+        PhysAddr(addr)
+    }
+
+    pub const fn as_u64(self) -> u64 {
+        self.0
+    }
+
+    pub const fn as_usize(self) -> usize {
+        self.0 as usize
+    }
+
+    pub const fn is_page_aligned(self) -> bool {
+        self.0 & PAGE_OFF_MASK == 0
+    }
+
+    pub const fn offset(self, n: u64) -> Self {
+        PhysAddr(self.0 + n)
+    }
+}
+
+#[repr(transparent)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct VirtAddr(u64);
+
+impl VirtAddr {
+    pub const fn new(addr: u64) -> Self {
+        VirtAddr(addr)
+    }
+
+    pub const fn as_u64(self) -> u64 {
+        self.0
+    }
+
+    pub const fn as_usize(self) -> usize {
+        self.0 as usize
+    }
+
+    pub const fn is_page_aligned(self) -> bool {
+        self.0 & PAGE_OFF_MASK == 0
+    }
+
+    pub const fn page_offset(self) -> u64 {
+        self.0 & PAGE_OFF_MASK
+    }
+
+    pub const fn offset(self, n: u64) -> Self {
+        VirtAddr(self.0 + n)
+    }
+
+    // index(level)
+    //    Index of this address in a page table of `level`, where level 0
+    //    is the L4 page table and level 3 the L1 page table.
+    pub fn index(self, level: usize) -> usize {
+        pageindex(self.0 as usize, level)
+    }
+}
+
+#[repr(transparent)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct PageTableEntry(X86_64PageentryT);
+
+impl PageTableEntry {
+    pub const fn empty() -> Self {
+        PageTableEntry(0)
+    }
+
+    pub fn new(pa: PhysAddr, flags: PteFlags) -> Self {
+        PageTableEntry(pa.as_u64() | flags.bits())
+    }
+
+    pub const fn from_bits(bits: X86_64PageentryT) -> Self {
+        PageTableEntry(bits)
+    }
+
+    pub const fn bits(self) -> X86_64PageentryT {
+        self.0
+    }
+
+    pub fn addr(self) -> PhysAddr {
+        PhysAddr(pte_addr(self.0))
+    }
+
+    pub fn flags(self) -> PteFlags {
+        PteFlags::from_bits_retain(self.0 & !PTE_ADDR_MASK)
+    }
+
+    pub fn is_present(self) -> bool {
+        self.0 & PTE_P != 0
+    }
+}
+
+pub struct PageTable {
+    root: NonNull<x86_64_pagetable>,
+    owner: i8,      // owner of the page table pages
+}
+
+impl PageTable {
+    // from_raw(pagetable)
+    //    Wrap the L4 page table `pagetable`. Returns `None` for a null or
+    //    misaligned pointer.
+    /// # Safety
+    /// `pagetable` and every page table it refers to must be identity-mapped
+    /// page table pages that stay alive, and not be walked through another
+    /// `PageTable` at the same time.
+    pub unsafe fn from_raw(pagetable: *mut x86_64_pagetable) -> Option<Self> {
+        if pagetable as u64 & PAGE_OFF_MASK != 0 {
+            return None;
+        }
+        let root = NonNull::new(pagetable)?;
+        let owner = pageinfo[page_number(pagetable as u64) as usize].owner;
+        Some(PageTable { root, owner })
+    }
+
+    // walk(va, flags)
+    //    Return the entries of the L1 page table that covers `va`. Every
+    //    entry on the way must be present and, if `flags` asks for
+    //    WRITABLE or USER, grant it. Fails with `HugePage` if `va` is
+    //    inside a large page, which has no L1 page table.
+    pub fn walk(&mut self, va: VirtAddr, flags: PteFlags)
+        -> Result<&mut [PageTableEntry; 512], PageTableError>
+    {
+        let pt = self.table(va, 1, flags, false)?;
+        // SAFETY: `PageTableEntry` is a transparent `X86_64PageentryT`
+        Ok(unsafe { &mut *((*pt).entry.as_mut_ptr() as *mut [PageTableEntry; 512]) })
+    }
+
+    // table(va, level, flags, alloc)
+    //    Walk down to the L`level` page table that covers `va`, checking
+    //    entries as described for `walk`. With `alloc`, missing page
+    //    tables are allocated instead of reported.
+    fn table(&mut self, va: VirtAddr, level: usize, flags: PteFlags, alloc: bool)
+        -> Result<*mut x86_64_pagetable, PageTableError>
+    {
+        let want = flags & (PteFlags::WRITABLE | PteFlags::USER);
+        let mut pt = self.root.as_ptr();
+        for index in 0..4 - level {
+            // SAFETY: `pt` is a page table page of this hierarchy
+            let mut pe = unsafe { PageTableEntry((*pt).entry[va.index(index)]) };
+            if !pe.is_present() {
+                if !alloc {
+                    return Err(PageTableError::MissingTable(3 - index));
+                }
+                pe = self.allocate_table()?;
+                // SAFETY: `pt` is a page table page of this hierarchy
+                unsafe { (*pt).entry[va.index(index)] = pe.bits() };
+            }
+            if pe.flags().contains(PteFlags::HUGE) {
+                return Err(PageTableError::HugePage(4 - index));
+            }
+            if pe.addr().as_u64() >= MEMSIZE_PHYSICAL {
+                return Err(PageTableError::OutOfRange);
+            }
+            if !pe.flags().contains(want) {
+                return Err(PageTableError::PermissionDenied(4 - index));
+            }
+            pt = pe.addr().as_u64() as *mut x86_64_pagetable;
+        }
+        Ok(pt)
+    }
+
+    // allocate_table()
+    //    Allocate a zeroed page table page and return an entry pointing to
+    //    it. Whether a page is accessible is decided at the last level, so
+    //    the entry grants everything.
+    fn allocate_table(&mut self) -> Result<PageTableEntry, PageTableError> {
+        // SAFETY: the frame allocator hands out unused, identity-mapped pages
+        unsafe {
+            let addr = FRAMES.allocate(self.owner).map_err(|_| PageTableError::OutOfMemory)?;
+            core::ptr::write_bytes(addr as *mut x86_64_pagetable, 0, 1);
+            Ok(PageTableEntry::new(PhysAddr(addr as u64),
+                                   PteFlags::PRESENT | PteFlags::WRITABLE | PteFlags::USER))
+        }
+    }
+
+    // set(va, entry)
+    //    Replace the L1 entry for `va` with `entry` and return the old one.
+    //    A 2 MB page covering `va` is split first.
+    pub fn set(&mut self, va: VirtAddr, entry: PageTableEntry)
+        -> Result<PageTableEntry, PageTableError>
+    {
+        if !va.is_page_aligned() {
+            return Err(PageTableError::Misaligned);
+        }
+        if let Err(PageTableError::HugePage(2)) = self.walk(va, PteFlags::empty()) {
+            self.split(va)?;
+        }
+        let l1 = self.table(va, 1, entry.flags(), entry.is_present())?;
+        // SAFETY: `l1` is a page table page of this hierarchy
+        let old = unsafe { core::mem::replace(&mut (*l1).entry[va.index(3)], entry.bits()) };
+        flush(va);
+        Ok(PageTableEntry(old))
+    }
+
+    // map_large(va, pa, flags)
+    //    Map the 2 MB page at `va` to the physical range starting at `pa`
+    //    with `flags`. PRESENT and HUGE are implied. Fails with
+    //    `TablePresent` if `va` is already covered by an L1 page table.
+    pub fn map_large(&mut self, va: VirtAddr, pa: PhysAddr, flags: PteFlags)
+        -> Result<(), PageTableError>
+    {
+        if !va.as_u64().is_multiple_of(LARGE_PAGESIZE) || !pa.as_u64().is_multiple_of(LARGE_PAGESIZE) {
+            return Err(PageTableError::Misaligned);
+        }
+        if pa.as_u64() + LARGE_PAGESIZE > MEMSIZE_PHYSICAL {
+            return Err(PageTableError::OutOfRange);
+        }
+        let l2 = self.table(va, 2, flags, true)?;
+        // SAFETY: `l2` is a page table page of this hierarchy
+        let pe = unsafe { &mut (*l2).entry[va.index(2)] };
+        if *pe & PTE_P != 0 && *pe & PTE_PS == 0 {
+            return Err(PageTableError::TablePresent);
+        }
+        *pe = PageTableEntry::new(pa, flags | PteFlags::PRESENT | PteFlags::HUGE).bits();
+        flush(va);
+        Ok(())
+    }
+
+    // split(va)
+    //    Replace the 2 MB page covering `va` with a new L1 page table that
+    //    maps the same memory with the same permissions in 4 KB pages.
+    pub fn split(&mut self, va: VirtAddr) -> Result<(), PageTableError> {
+        let l2 = self.table(va, 2, PteFlags::empty(), false)?;
+        // SAFETY: `l2` is a page table page of this hierarchy
+        let pe = unsafe { &mut (*l2).entry[va.index(2)] };
+        let large = PageTableEntry(*pe);
+        if !large.is_present() || !large.flags().contains(PteFlags::HUGE) {
+            return Err(PageTableError::NotMapped);
+        }
+
+        let l1 = self.allocate_table()?.addr().as_u64() as *mut x86_64_pagetable;
+        let base = large.addr().as_u64() & !(LARGE_PAGESIZE - 1);
+        let flags = large.flags() - PteFlags::HUGE;
+        // SAFETY: `l1` is a fresh page table page of this hierarchy
+        for (i, entry) in unsafe { (*l1).entry.iter_mut() }.enumerate() {
+            *entry = PageTableEntry::new(PhysAddr(base + i as u64 * PAGESIZE), flags).bits();
+        }
+        *pe = PageTableEntry::new(PhysAddr(l1 as u64),
+                                  flags & (PteFlags::PRESENT | PteFlags::WRITABLE | PteFlags::USER)).bits();
+        flush(va);
+        Ok(())
+    }
+
+    // map(va, pa, flags)
+    //    Map the page at `va` to the physical page `pa` with `flags`.
+    //    PRESENT is implied.
+    pub fn map(&mut self, va: VirtAddr, pa: PhysAddr, flags: PteFlags)
+        -> Result<(), PageTableError>
+    {
+        if !pa.is_page_aligned() {
+            return Err(PageTableError::Misaligned);
+        }
+        if pa.as_u64() >= MEMSIZE_PHYSICAL {
+            return Err(PageTableError::OutOfRange);
+        }
+        self.set(va, PageTableEntry::new(pa, flags | PteFlags::PRESENT))?;
+        Ok(())
+    }
+
+    // map_range(va, pa, sz, flags)
+    //    Map `[va, va+sz)` to `[pa, pa+sz)` with `flags`. Stops at the
+    //    first page that cannot be mapped; pages before it stay mapped.
+    pub fn map_range(&mut self, va: VirtAddr, pa: PhysAddr, sz: u64, flags: PteFlags)
+        -> Result<(), PageTableError>
+    {
+        if sz & PAGE_OFF_MASK != 0 {
+            return Err(PageTableError::Misaligned);
+        }
+        let mut off = 0;
+        while off < sz {
+            self.map(va.offset(off), pa.offset(off), flags)?;
+            off += PAGESIZE;
+        }
+        Ok(())
+    }
+
+    // unmap(va)
+    //    Remove the mapping of the page at `va` and return the entry that
+    //    mapped it. Fails with `NotMapped` if nothing is mapped there.
+    pub fn unmap(&mut self, va: VirtAddr) -> Result<PageTableEntry, PageTableError> {
+        if !va.is_page_aligned() {
+            return Err(PageTableError::Misaligned);
+        }
+        self.translate(va)?;
+        self.set(va, PageTableEntry::empty())
+    }
+
+    // unmap_range(va, sz, release)
+    //    Remove every mapping in `[va, va+sz)` and call `release(va, entry)`
+    //    with the old entry of each page that was mapped. Page tables left
+    //    empty are freed afterwards, except in the kernel's page table,
+    //    whose page tables may be static.
+    pub fn unmap_range(&mut self, va: VirtAddr, sz: u64,
+                       mut release: impl FnMut(VirtAddr, PageTableEntry))
+        -> Result<(), PageTableError>
+    {
+        if !va.is_page_aligned() || sz & PAGE_OFF_MASK != 0 {
+            return Err(PageTableError::Misaligned);
+        }
+        let mut off = 0;
+        while off < sz {
+            match self.unmap(va.offset(off)) {
+                Ok(old) => release(va.offset(off), old),
+                Err(PageTableError::NotMapped | PageTableError::MissingTable(_)) => {}
+                Err(e) => return Err(e),
+            }
+            off += PAGESIZE;
+        }
+
+        if self.owner != PageOwner::PoKernel as i8 {
+            // one L1 page table covers LARGE_PAGESIZE bytes
+            let mut region = va.as_u64() & !(LARGE_PAGESIZE - 1);
+            while region < va.as_u64() + sz {
+                self.prune(VirtAddr(region));
+                region += LARGE_PAGESIZE;
+            }
+        }
+        Ok(())
+    }
+
+    // protect_range(va, sz, flags)
+    //    Change the permissions of every page mapped in `[va, va+sz)` to
+    //    `flags`, keeping the physical pages. PRESENT is implied; unmapped
+    //    pages are skipped.
+    pub fn protect_range(&mut self, va: VirtAddr, sz: u64, flags: PteFlags)
+        -> Result<(), PageTableError>
+    {
+        if !va.is_page_aligned() || sz & PAGE_OFF_MASK != 0 {
+            return Err(PageTableError::Misaligned);
+        }
+        let mut off = 0;
+        while off < sz {
+            match self.translate(va.offset(off)) {
+                Ok(t) => {
+                    let pa = PhysAddr(t.pa.as_u64() & !PAGE_OFF_MASK);
+                    self.set(va.offset(off), PageTableEntry::new(pa, flags | PteFlags::PRESENT))?;
+                }
+                Err(PageTableError::NotMapped | PageTableError::MissingTable(_)) => {}
+                Err(e) => return Err(e),
+            }
+            off += PAGESIZE;
+        }
+        Ok(())
+    }
+
+    // prune(va)
+    //    Free the L1, L2 and L3 page tables on the path to `va`, bottom up,
+    //    as long as they have no present entries. The L4 page table stays.
+    fn prune(&mut self, va: VirtAddr) {
+        let mut path = [self.root.as_ptr(); 4];
+        let mut depth = 0;
+        while depth < 3 {
+            // SAFETY: `path[depth]` is a page table page of this hierarchy
+            let pe = unsafe { PageTableEntry((*path[depth]).entry[va.index(depth)]) };
+            if !pe.is_present() || pe.flags().contains(PteFlags::HUGE) {
+                break;
+            }
+            path[depth + 1] = pe.addr().as_u64() as *mut x86_64_pagetable;
+            depth += 1;
+        }
+
+        for index in (1..=depth).rev() {
+            let pt = path[index];
+            // SAFETY: as above
+            unsafe {
+                if (*pt).entry.iter().any(|&pe| pe & PTE_P != 0) {
+                    return;
+                }
+                (*path[index - 1]).entry[va.index(index - 1)] = 0;
+                FRAMES.free(pt as usize).unwrap();
+            }
+            flush(va);
+        }
+    }
+
+    // translate(va)
+    //    Return where `va` is mapped. 2 MB pages (PTE_PS in an L2 entry)
+    //    and 1 GB pages (PTE_PS in an L3 entry) end the walk early. Like
+    //    the processor, WRITABLE and USER must be granted at every level,
+    //    and NO_EXECUTE at any level applies to the page. Fails
+    //    with `MissingTable(n)` if the walk stopped because the Ln page
+    //    table is absent, or with `NotMapped` if it reached the leaf entry
+    //    and found it empty.
+    pub fn translate(&self, va: VirtAddr) -> Result<Translation, PageTableError> {
+        let mut inherited = PteFlags::all();
+        let mut no_execute = PteFlags::empty();
+        let mut pt = self.root.as_ptr();
+        for index in 0..=3 {
+            // SAFETY: `pt` is a page table page of this hierarchy
+            let pe = unsafe { PageTableEntry((*pt).entry[va.index(index)]) };
+            let level = 4 - index;
+            if !pe.is_present() {
+                return Err(if level == 1 {
+                    PageTableError::NotMapped
+                } else {
+                    PageTableError::MissingTable(level - 1)
+                });
+            }
+            inherited &= pe.flags() | !(PteFlags::WRITABLE | PteFlags::USER);
+            no_execute |= pe.flags() & PteFlags::NO_EXECUTE;
+
+            if level == 1 || (pe.flags().contains(PteFlags::HUGE) && level <= 3) {
+                let size = level_page_size(level);
+                let base = pe.addr().as_u64() & !(size - 1);
+                return Ok(Translation {
+                    pa: PhysAddr(base + (va.as_u64() & (size - 1))),
+                    flags: (pe.flags() & inherited) | no_execute,
+                    level,
+                });
+            }
+            pt = pe.addr().as_u64() as *mut x86_64_pagetable;
+        }
+        unreachable!()
+    }
+}
+
+// Result of `PageTable::translate`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Translation {
+    pub pa: PhysAddr,       // physical address of the translated byte
+    pub flags: PteFlags,    // permissions granted at every level of the walk
+    pub level: usize,       // level of the leaf entry: 1 (4 KB), 2 (2 MB), 3 (1 GB)
+}
+
+impl Translation {
+    pub fn page_size(&self) -> u64 {
+        level_page_size(self.level)
+    }
+
+    // to_vamapping()
+    //    Convert to the `vamapping` C code and `sys_mapping` use. `pn` is
+    //    the 4 KB physical page holding the byte, also inside large pages.
+    pub fn to_vamapping(&self) -> VAMapping {
+        VAMapping {
+            pn: page_number(self.pa.as_u64()),
+            pa: self.pa.as_u64(),
+            perm: (self.flags.bits() & PTE_FLAGS_MASK) as i32,
+        }
+    }
+}
+
+// level_page_size(level)
+//    Bytes mapped by one entry of an L`level` page table.
+fn level_page_size(level: usize) -> u64 {
+    PAGESIZE << ((level - 1) * PAGEINDEXBITS as usize)
+}
+
+// flush(va)
+//    Drop the TLB entry for `va`. `invlpg` also drops cached upper-level
+//    page table entries, so this covers freed page tables too. Flushing an
+//    address of a page table that is not loaded is harmless.
+fn flush(va: VirtAddr) {
+    x86_64::instructions::tlb::flush(x86_64::VirtAddr::new_truncate(va.as_u64()));
+}
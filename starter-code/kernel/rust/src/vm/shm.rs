@@ -0,0 +1,196 @@
+// shm.rs
+//
+//    Shared memory segments.
+
+use crate::vm::*;
+use crate::vm::vm::{unmap_range, virtual_memory_map, PTE_SHM};
+use crate::frame::frame::FRAMES;
+use crate::kernel::kernel::PageOwner;
+use alloc::vec::Vec;
+
+// SHARED MEMORY SEGMENTS
+//
+//    A segment is a set of zeroed physical pages that sys_shm_attach maps
+//    into any number of processes, at whatever address each of them picks.
+//    The pages belong to the kernel: the segment holds one reference to
+//    each of them and every attachment adds one, so `pageinfo[].refcount`
+//    is 1 plus the number of attachments. Attached pages carry PTE_SHM so
+//    that fork shares them as they are instead of making them
+//    copy-on-write; the child inherits the parent's attachments.
+//
+//    A segment is destroyed and its pages freed when its last attachment
+//    goes away, through sys_shm_detach or exit. A segment that was never
+//    attached goes away when the process that created it exits.
+//
+//    Segment ids are indexes into `SEGMENTS`.
+
+pub const NSHM: usize = 16;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Attachment {
+    pub pid: PidT,      // attached process
+    pub va: usize,      // where the segment starts in that process
+}
+
+pub struct ShmSegment {
+    pub pages: Vec<usize>,              // physical pages, empty if unused
+    pub creator: PidT,                  // process that created the segment
+    pub attachments: Vec<Attachment>,
+    attached: bool,                     // attached at least once
+}
+
+pub static mut SEGMENTS: [ShmSegment; NSHM] = [const { ShmSegment::new() }; NSHM];
+
+// shm_init()
+//    Mark every segment unused. Must run before heap_init(): after a soft
+//    reboot the old segments point into the previous heap and must not be
+//    dropped.
+
+/// # Safety
+/// Any segment from before a soft reboot is leaked; must run before
+/// heap_init().
+pub unsafe fn shm_init() {
+    core::ptr::addr_of_mut!(SEGMENTS).write([const { ShmSegment::new() }; NSHM]);
+}
+
+impl ShmSegment {
+    const fn new() -> Self {
+        ShmSegment { pages: Vec::new(), creator: 0, attachments: Vec::new(), attached: false }
+    }
+
+    pub fn in_use(&self) -> bool {
+        !self.pages.is_empty()
+    }
+
+    pub fn size(&self) -> usize {
+        self.pages.len() * PAGESIZE as usize
+    }
+}
+
+// shm_create(size, creator)
+//    Create a segment of `size` bytes, rounded up to whole pages, for
+//    process `creator`. Returns its id, or `None` if all segments are in
+//    use or memory runs out.
+
+/// # Safety
+/// Allocates physical pages and heap memory; the frame allocator and heap must
+/// be initialized.
+pub unsafe fn shm_create(size: usize, creator: PidT) -> Option<usize> {
+    let id = SEGMENTS.iter().position(|s| !s.in_use())?;
+    let npages = size.div_ceil(PAGESIZE as usize);
+    let mut pages = Vec::with_capacity(npages);
+    for _ in 0..npages {
+        match FRAMES.allocate(PageOwner::PoKernel as i8) {
+            Ok(pa) => {
+                core::ptr::write_bytes(pa as *mut u8, 0, PAGESIZE as usize);
+                pages.push(pa);
+            }
+            Err(_) => {
+                for pa in pages {
+                    FRAMES.free(pa).unwrap();
+                }
+                return None;
+            }
+        }
+    }
+    SEGMENTS[id] = ShmSegment { pages, creator, attachments: Vec::new(), attached: false };
+    Some(id)
+}
+
+// shm_attach(id, pid, pagetable, va)
+//    Map segment `id` read-write at `va` in `pagetable`, the page table of
+//    process `pid`. The caller checks that the range is free. Returns false
+//    if there is no such segment or page tables cannot be allocated.
+
+/// # Safety
+/// `pagetable` must be the valid page table of process `pid`.
+pub unsafe fn shm_attach(id: usize, pid: PidT, pagetable: *mut x86_64_pagetable, va: usize) -> bool {
+    if id >= NSHM || !SEGMENTS[id].in_use() {
+        return false;
+    }
+    let seg = &mut SEGMENTS[id];
+    let perm = (PTE_P | PTE_W | PTE_U | PTE_SHM) as i32;
+    for (i, &pa) in seg.pages.iter().enumerate() {
+        let addr = va + i * PAGESIZE as usize;
+        if virtual_memory_map(pagetable, addr, pa, PAGESIZE as usize, perm) < 0 {
+            unmap_range(pagetable, va, i * PAGESIZE as usize, |_, pe| {
+                FRAMES.free(pe.addr().as_usize()).unwrap();
+            }).unwrap();
+            return false;
+        }
+        FRAMES.share(pa).unwrap();
+    }
+    seg.attachments.push(Attachment { pid, va });
+    seg.attached = true;
+    true
+}
+
+// shm_detach(pid, pagetable, va)
+//    Unmap the segment attached at `va` by process `pid`, whose page table
+//    is `pagetable`, destroying the segment if this was its last
+//    attachment. Returns false if no segment is attached at `va`.
+
+/// # Safety
+/// `pagetable` must be the valid page table of process `pid`.
+pub unsafe fn shm_detach(pid: PidT, pagetable: *mut x86_64_pagetable, va: usize) -> bool {
+    let attachment = Attachment { pid, va };
+    let Some(id) = SEGMENTS.iter().position(|s| s.attachments.contains(&attachment)) else {
+        return false;
+    };
+    let seg = &mut SEGMENTS[id];
+    unmap_range(pagetable, va, seg.size(), |_, pe| {
+        FRAMES.free(pe.addr().as_usize()).unwrap();
+    }).unwrap();
+    seg.attachments.retain(|a| *a != attachment);
+    if seg.attachments.is_empty() {
+        shm_destroy(id);
+    }
+    true
+}
+
+// shm_fork(parent, child)
+//    Give process `child` the attachments of `parent`. Fork has already
+//    mapped the pages and counted the references.
+
+/// # Safety
+/// `child` must map the same segment pages as `parent`.
+pub unsafe fn shm_fork(parent: PidT, child: PidT) {
+    for seg in SEGMENTS.iter_mut() {
+        let inherited: Vec<Attachment> = seg.attachments.iter()
+            .filter(|a| a.pid == parent)
+            .map(|a| Attachment { pid: child, va: a.va })
+            .collect();
+        seg.attachments.extend(inherited);
+    }
+}
+
+// shm_exit(pid, pagetable)
+//    Detach every segment process `pid` has attached, and destroy the
+//    segments it created that were never attached.
+
+/// # Safety
+/// `pagetable` must be the valid page table of process `pid`.
+pub unsafe fn shm_exit(pid: PidT, pagetable: *mut x86_64_pagetable) {
+    // shm_detach and shm_destroy change SEGMENTS, so no iterator over it
+    // may be live across them.
+    #[allow(clippy::needless_range_loop)]
+    for id in 0..NSHM {
+        while let Some(a) = SEGMENTS[id].attachments.iter().find(|a| a.pid == pid).copied() {
+            shm_detach(pid, pagetable, a.va);
+        }
+        let seg = &SEGMENTS[id];
+        if seg.in_use() && !seg.attached && seg.creator == pid {
+            shm_destroy(id);
+        }
+    }
+}
+
+// shm_destroy(id)
+//    Drop the segment's own references to its pages and free the slot.
+
+unsafe fn shm_destroy(id: usize) {
+    for &pa in SEGMENTS[id].pages.iter() {
+        FRAMES.free(pa).unwrap();
+    }
+    SEGMENTS[id] = ShmSegment::new();
+}
@@ -1,6 +1,15 @@
+use crate::*;
 use crate::vm::*;
-#[allow(unused_imports)]
 use crate::aux::traits::*;
+use crate::frame::frame::FRAMES;
+use crate::vm::pagetable::*;
+
+// Page table entry bits 9-11 are ignored by the processor and free for the
+// kernel to use. PTE_COW marks a page that fork made read-only because it
+// is shared; a write to it is resolved by copying the page. PTE_SHM marks
+// a page of a shared memory segment, which fork keeps shared as is.
+pub const PTE_COW: X86_64PageentryT = 0x200;
+pub const PTE_SHM: X86_64PageentryT = 0x400;
 
 // lookup_l1pagetable(pagetable, va, perm)
 //    Helper function to find the last level of `va` in `pagetable`
@@ -8,53 +17,31 @@ use crate::aux::traits::*;
 //    Returns an x86_64_pagetable pointer to the last level pagetable
 //    if it exists and can be accessed with the given permissions
 //    Returns NULL otherwise
+//
+//    C entry point for `PageTable::walk` (see pagetable.rs).
 
 #[no_mangle]
-#[allow(unused_variables)] // must be removed
 pub unsafe extern "C" fn lookup_l1pagetable(
     pagetable: *mut x86_64_pagetable,
     va: usize,
     perm: i32,
 ) -> *mut x86_64_pagetable {
-    let mut pt = pagetable;
-
-    // We find the L1 pagetable by doing the following three steps for each level
-    // 1. Find index to the next pagetable entry using the `va`
-    // 2. Check if this entry has the appropriate requested permissions
-    // 3. Repeat the steps till you reach the L1 pagetable (i.e., thrice)
-    // 4. Return the pagetable address
-
-    for i in 0..=2 {
-        // TODO
-        // find page entry by finding `ith` level index of va to index pagetable entries of `pt`
-        // you should read x86-64.h to understand relevant structs and macros to make this part easier
-        let pe: u64 = 0; // replace this
-
-        if (pe & PTE_P as u64) == 0
-        { // address of next level should be present AND PTE_P should be set, error otherwise
-            // HERE NEED TO ADD THE LOGPRINTF
-            if (perm & PTE_P as i32) == 0 {
-                return core::ptr::null_mut();
-            }
-            // HERE NEED TO ADD THE LOGPRINTF
-            return core::ptr::null_mut();
-        }
+    let Some(mut pt) = PageTable::from_raw(pagetable) else {
+        return core::ptr::null_mut();
+    };
+    let flags = PteFlags::from_bits_retain(perm as X86_64PageentryT);
 
-        // sanity-check page entry and permissions
-        assert!(pte_addr(pe) < MEMSIZE_PHYSICAL as u64); // at sensible address
-        if (perm & PTE_W as i32) != 0 {
-            assert!((pe & PTE_W as u64) != 0); // if requester wants PTE_W, entry must allow PTE_W
+    match pt.walk(VirtAddr::new(va as u64), flags) {
+        Ok(l1) => l1.as_mut_ptr() as *mut x86_64_pagetable,
+        Err(PageTableError::MissingTable(_)) if !flags.contains(PteFlags::PRESENT) => {
+            // unmapping an address that was never mapped is not an error
+            core::ptr::null_mut()
         }
-        if (perm & PTE_U as i32) != 0 {
-            assert!((pe & PTE_U as u64) != 0); // if requester wants PTE_U, entry must allow PTE_U
+        Err(e) => {
+            c_log!("lookup_l1pagetable: ", e, " for ", va as *const u8, "\n");
+            core::ptr::null_mut()
         }
-
-        // TODO
-        // set pt to physical address to next pagetable using `pe`
-        pt = core::ptr::null_mut(); // replace this
     }
-
-    pt
 }
 
 // virtual_memory_map(pagetable, va, pa, sz, perm)
@@ -69,8 +56,17 @@ pub unsafe extern "C" fn lookup_l1pagetable(
 //    `PTE_W` (the memory is Writable), and `PTE_U` (the memory may be
 //    accessed by User applications). If `!(perm & PTE_P)`, `pa` is ignored.
 //
+//    With `PTE_PS` in `perm`, parts of the range where `va` and `pa` are
+//    2 MB-aligned are mapped with 2 MB pages, the rest with 4 KB pages.
+//    This is meant for kernel-only ranges and may not be combined with
+//    `PTE_U`. Mapping 4 KB pages inside a 2 MB page splits it.
+//
+//    Missing page tables are allocated on demand.
+//
 //    Returns 0 if the map succeeds, -1 if it fails (because a required
-//    page table was not allocated).
+//    page table could not be allocated).
+//
+//    C entry point for `PageTable::map_range` (see pagetable.rs).
 
 #[no_mangle]
 pub unsafe fn virtual_memory_map(
@@ -92,39 +88,229 @@ pub unsafe fn virtual_memory_map(
     assert!(perm >= 0 && perm < 0x1000);         // `perm` makes sense (perm can only be 12 bits)
     assert!((pagetable as usize) % PAGESIZE as usize == 0); // `pagetable` page-aligned
 
-    let mut last_index123 = -1 as i32;
-    
-    #[allow(unused_mut)]
-    let mut l1pagetable: *mut x86_64_pagetable = core::ptr::null_mut();
-
-    #[allow(unused_variables)]
-    let (mut va, mut pa, mut sz) = (va, pa, sz); // clone
-    // for each page-aligned address, set the appropriate page entry
-    while sz != 0 {
-        let cur_index123 = (va >> (PAGEOFFBITS + PAGEINDEXBITS)) as i32;
-        if cur_index123 != last_index123 {
-            // TODO
-            // find pointer to last level pagetable for current va
+    if perm & PTE_PS as i32 != 0 {
+        assert!(perm & PTE_U as i32 == 0);       // large pages are kernel-only
+    }
 
+    let Some(mut pt) = PageTable::from_raw(pagetable) else {
+        return -1;
+    };
+    let va = VirtAddr::new(va as u64);
+    let mut flags = PteFlags::from_bits_retain(perm as X86_64PageentryT);
+    let large = flags.contains(PteFlags::HUGE);
+    flags.remove(PteFlags::HUGE);
 
-            
-            last_index123 = cur_index123;
+    if flags.contains(PteFlags::PRESENT) {
+        let pa = PhysAddr::new(pa as u64);
+        let mut off = 0;
+        while off < sz as u64 {
+            // use a 2 MB page wherever the range allows one
+            let r = if large
+                && (va.as_u64() + off).is_multiple_of(LARGE_PAGESIZE)
+                && (pa.as_u64() + off).is_multiple_of(LARGE_PAGESIZE)
+                && sz as u64 - off >= LARGE_PAGESIZE
+                && pt.map_large(va.offset(off), pa.offset(off), flags).is_ok() {
+                off += LARGE_PAGESIZE;
+                Ok(())
+            } else {
+                let r = pt.map(va.offset(off), pa.offset(off), flags);
+                off += PAGESIZE;
+                r
+            };
+            if let Err(e) = r {
+                c_log!("virtual_memory_map: ", e, " for ", va.as_usize() as *const u8, "\n");
+                return -1;
+            }
         }
+        return 0;
+    }
+
+    // store `perm` in the entries that exist; missing page tables mean
+    // there is nothing to unmap
+    let mut off = 0;
+    while off < sz as u64 {
+        let _ = pt.set(va.offset(off), PageTableEntry::from_bits(perm as X86_64PageentryT));
+        off += PAGESIZE;
+    }
+    0
+}
+
+// map_range(pagetable, va, pa, sz, perm)
+//    Map `[va, va+sz)` to `[pa, pa+sz)` in `pagetable` with `perm`.
+//    Unlike `virtual_memory_map`, `perm` may hold flags above bit 11, such
+//    as NO_EXECUTE.
+
+/// # Safety
+/// `pagetable` must be a valid L4 page table and `pa` memory the caller may
+/// hand out.
+pub unsafe fn map_range(
+    pagetable: *mut x86_64_pagetable,
+    va: usize,
+    pa: usize,
+    sz: usize,
+    perm: PteFlags,
+) -> Result<(), PageTableError> {
+    let mut pt = PageTable::from_raw(pagetable).ok_or(PageTableError::Misaligned)?;
+    pt.map_range(VirtAddr::new(va as u64), PhysAddr::new(pa as u64), sz as u64, perm)
+}
+
+// translate(pagetable, va)
+//    Return where `va` is mapped in `pagetable`, with all of its flags.
+//    `virtual_memory_lookup` is the C view of the same walk, whose `perm`
+//    only has room for bits 0-11.
+
+/// # Safety
+/// `pagetable` must be a valid L4 page table.
+pub unsafe fn translate(
+    pagetable: *mut x86_64_pagetable,
+    va: usize,
+) -> Result<Translation, PageTableError> {
+    let pt = PageTable::from_raw(pagetable).ok_or(PageTableError::Misaligned)?;
+    pt.translate(VirtAddr::new(va as u64))
+}
+
+// unmap_range(pagetable, va, sz, release)
+//    Remove the mappings for `[va, va+sz)` from `pagetable`. `release(va,
+//    entry)` is called with the old entry of every page that was mapped,
+//    after the page is gone from the page table and the TLB; dropping the
+//    physical page is up to the caller. L1-L3 page tables that end up
+//    empty are freed.
+
+/// # Safety
+/// `pagetable` must be a valid L4 page table.
+pub unsafe fn unmap_range(
+    pagetable: *mut x86_64_pagetable,
+    va: usize,
+    sz: usize,
+    release: impl FnMut(VirtAddr, PageTableEntry),
+) -> Result<(), PageTableError> {
+    let mut pt = PageTable::from_raw(pagetable).ok_or(PageTableError::Misaligned)?;
+    pt.unmap_range(VirtAddr::new(va as u64), sz as u64, release)
+}
 
-        if perm & PTE_P as i32 != 0 && !l1pagetable.is_null() {
-            // TODO
-            // map `pa` at appropriate entry with permissions `perm`
-        } else if !l1pagetable.is_null() {
-            // TODO
-            // map to address 0 with `perm`
-        } else if perm & PTE_P as i32 != 0 {
-            // error, no allocated l1 page found for va
-            return -1;
+// protect_range(pagetable, va, sz, perm)
+//    Set the permissions of every page mapped in `[va, va+sz)` to `perm`.
+//    Unmapped pages are skipped.
+
+/// # Safety
+/// `pagetable` must be a valid L4 page table. Making kernel memory user-
+/// accessible exposes it.
+pub unsafe fn protect_range(
+    pagetable: *mut x86_64_pagetable,
+    va: usize,
+    sz: usize,
+    perm: PteFlags,
+) -> Result<(), PageTableError> {
+    let mut pt = PageTable::from_raw(pagetable).ok_or(PageTableError::Misaligned)?;
+    pt.protect_range(VirtAddr::new(va as u64), sz as u64, perm)
+}
+
+// virtual_memory_lookup(pagetable, va)
+//    Returns information about the mapping of the virtual address `va` in
+//    `pagetable`. The information is returned as a `vamapping` object:
+//    `pa` is the physical address of the byte at `va`, `pn` the physical
+//    page holding it and `perm` the permissions granted at every level.
+//    `pn` is -1 if `va` is not mapped.
+//
+//    C entry point for `PageTable::translate` (see pagetable.rs), which
+//    also follows 2 MB and 1 GB large pages.
+
+/// # Safety
+/// Called from C; `pagetable` must be a valid L4 page table.
+#[no_mangle]
+pub unsafe extern "C" fn virtual_memory_lookup(
+    pagetable: *mut x86_64_pagetable,
+    va: usize,
+) -> VAMapping {
+    match PageTable::from_raw(pagetable) {
+        Some(pt) => match pt.translate(VirtAddr::new(va as u64)) {
+            Ok(t) => t.to_vamapping(),
+            Err(_) => VAMapping::default(),
+        },
+        None => VAMapping::default(),
+    }
+}
+
+// copy_pagetable(owner)
+//    Allocate a fresh four-level page table hierarchy for process `owner`
+//    and copy the kernel's mappings below PROC_START_ADDR into it. Kernel
+//    memory is mapped without PTE_U so the process cannot touch it; the
+//    console is the only exception, since applications print to it directly.
+//
+//    The hierarchy has one L4, L3 and L2 page table, and two L1 page tables
+//    covering `[0, MEMSIZE_VIRTUAL)`. Unlike the kernel's, it uses only
+//    4 KB pages, so kernel memory can be hidden page by page.
+//    Every page table page is owned by `owner` with refcount 1.
+//
+//    Returns the new L4 page table, or NULL if physical memory ran out
+//    (no pages stay allocated in that case).
+
+/// # Safety
+/// The kernel page table and the frame allocator must be initialized.
+pub unsafe fn copy_pagetable(owner: i8) -> *mut x86_64_pagetable {
+    let mut pts: [*mut x86_64_pagetable; 5] = [core::ptr::null_mut(); 5];
+    for i in 0..pts.len() {
+        match FRAMES.allocate(owner) {
+            Ok(addr) => {
+                pts[i] = addr as *mut x86_64_pagetable;
+                core::ptr::write_bytes(pts[i], 0, 1);
+            }
+            Err(_) => {
+                // out of memory, give back what we took
+                for pt in &pts[..i] {
+                    FRAMES.free(*pt as usize).unwrap();
+                }
+                return core::ptr::null_mut();
+            }
         }
+    }
 
+    // connect the pagetable pages
+    let perm = PTE_P | PTE_W | PTE_U;
+    (*pts[0]).entry[0] = pts[1] as u64 | perm;
+    (*pts[1]).entry[0] = pts[2] as u64 | perm;
+    (*pts[2]).entry[0] = pts[3] as u64 | perm;
+    (*pts[2]).entry[1] = pts[4] as u64 | perm;
+
+    // copy kernel mappings, hiding everything but the console from the process
+    let console_addr = console.as_ptr() as usize;
+    let mut va: usize = 0;
+    while va < PROC_START_ADDR as usize {
+        let vam = virtual_memory_lookup(kernel_pagetable, va);
+        if vam.pn >= 0 {
+            let perm = if va == console_addr {
+                PTE_P | PTE_W | PTE_U
+            } else {
+                vam.perm as u64 & (PTE_P | PTE_W)
+            };
+            let r = virtual_memory_map(pts[0], va, vam.pa as usize, PAGESIZE as usize, perm as i32);
+            assert!(r == 0);
+        }
         va += PAGESIZE as usize;
-        pa += PAGESIZE as usize;
-        sz -= PAGESIZE as usize;
     }
-    return 0;
+
+    pts[0]
+}
+
+// free_pagetable(pagetable)
+//    Free the page table pages of `pagetable`, from the L1 page tables up to
+//    the L4 page table itself. Pages mapped by the L1 entries are left alone;
+//    callers must drop their references first.
+
+/// # Safety
+/// `pagetable` must not be loaded in %cr3 or used again.
+pub unsafe fn free_pagetable(pagetable: *mut x86_64_pagetable) {
+    free_pagetable_level(pagetable, 0);
+}
+
+unsafe fn free_pagetable_level(pt: *mut x86_64_pagetable, level: usize) {
+    if level < 3 {
+        for index in 0..NPAGETABLEENTRIES as usize {
+            let pe = (*pt).entry[index];
+            if (pe & PTE_P) != 0 {
+                free_pagetable_level(pte_addr(pe) as *mut x86_64_pagetable, level + 1);
+            }
+        }
+    }
+    FRAMES.free(pt as usize).unwrap();
 }
@@ -0,0 +1,195 @@
+// vma.rs
+//
+//    Per-process lists of anonymous memory regions created by sys_mmap.
+
+use crate::vm::*;
+use crate::vm::pagetable::PteFlags;
+use alloc::vec::Vec;
+
+// VIRTUAL MEMORY AREAS
+//
+//    Each process has a list of `Vma`s, the page-aligned ranges it got from
+//    sys_mmap, sorted by address and never overlapping. Pages inside a
+//    `Vma` are mapped lazily: a missing page is only allocated and zeroed
+//    when the process first touches it (see `handle_vma_fault`). The `prot`
+//    of the `Vma` is what that page gets mapped with.
+//
+//    Pages that are mapped keep following `prot`: sys_mprotect rewrites
+//    their entries, and sys_munmap releases them. A `PROT_NONE` page that
+//    is already mapped stays mapped for the kernel (PTE_P without PTE_U),
+//    so its contents survive until it is made accessible again.
+//
+//    The lists live on the kernel heap, outside of `Proc`, which C shares.
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Vma {
+    pub start: usize,   // first address
+    pub end: usize,     // address past the end
+    pub prot: i32,      // PROT_READ/PROT_WRITE combination, or PROT_NONE
+}
+
+pub struct VmaList {
+    vmas: Vec<Vma>,
+}
+
+pub static mut VMAS: [VmaList; NPROC] = [const { VmaList::new() }; NPROC];
+
+// vma_init()
+//    Empty every process's list. Must run before heap_init(): after a soft
+//    reboot the old lists point into the previous heap and must not be
+//    dropped.
+
+/// # Safety
+/// Any list from before a soft reboot is leaked; must run before heap_init().
+pub unsafe fn vma_init() {
+    core::ptr::addr_of_mut!(VMAS).write([const { VmaList::new() }; NPROC]);
+}
+
+// prot_valid(prot)
+//    Return true if `prot` is a protection sys_mmap accepts. x86-64 cannot
+//    map a page write-only, so PROT_WRITE needs PROT_READ.
+
+pub fn prot_valid(prot: i32) -> bool {
+    prot == PROT_NONE || prot == PROT_READ || prot == PROT_READ | PROT_WRITE
+}
+
+// prot_flags(prot)
+//    Page table flags for a page of a region with protection `prot`.
+
+pub fn prot_flags(prot: i32) -> PteFlags {
+    let mut flags = PteFlags::PRESENT;
+    if prot & PROT_READ != 0 {
+        flags |= PteFlags::USER;
+    }
+    if prot & PROT_WRITE != 0 {
+        flags |= PteFlags::WRITABLE;
+    }
+    flags
+}
+
+impl Default for VmaList {
+    fn default() -> Self {
+        VmaList::new()
+    }
+}
+
+impl VmaList {
+    pub const fn new() -> Self {
+        VmaList { vmas: Vec::new() }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Vma> {
+        self.vmas.iter()
+    }
+
+    // find(addr)
+    //    Return the region containing `addr`, if any.
+    pub fn find(&self, addr: usize) -> Option<&Vma> {
+        self.vmas.iter().find(|v| v.start <= addr && addr < v.end)
+    }
+
+    // overlaps(start, end)
+    //    Return true if any region intersects `[start, end)`.
+    pub fn overlaps(&self, start: usize, end: usize) -> bool {
+        self.vmas.iter().any(|v| v.start < end && start < v.end)
+    }
+
+    // covers(start, end)
+    //    Return true if every address of `[start, end)` is in some region.
+    pub fn covers(&self, start: usize, end: usize) -> bool {
+        let mut addr = start;
+        for v in self.vmas.iter() {
+            if v.start > addr {
+                break;
+            }
+            if v.end > addr {
+                addr = v.end;
+            }
+        }
+        addr >= end
+    }
+
+    // find_gap(lo, hi, len, busy)
+    //    Return the highest `len`-byte range inside `[lo, hi)` that touches
+    //    no region and none of whose pages `busy` reports as taken.
+    pub fn find_gap(&self, lo: usize, hi: usize, len: usize,
+                    busy: impl Fn(usize) -> bool) -> Option<usize> {
+        let pagesize = PAGESIZE as usize;
+        let mut end = hi;
+        while end >= lo + len {
+            let start = end - len;
+            // move below the highest obstacle in the candidate range
+            let obstacle = self.vmas.iter()
+                .filter(|v| v.start < end && start < v.end)
+                .map(|v| v.start)
+                .min()
+                .or_else(|| (start..end).step_by(pagesize).rev().find(|&va| busy(va)));
+            match obstacle {
+                None => return Some(start),
+                Some(addr) => end = addr,
+            }
+        }
+        None
+    }
+
+    // insert(start, end, prot)
+    //    Add the region `[start, end)`, which must not overlap another one.
+    pub fn insert(&mut self, start: usize, end: usize, prot: i32) {
+        let index = self.vmas.partition_point(|v| v.start < start);
+        self.vmas.insert(index, Vma { start, end, prot });
+        self.merge();
+    }
+
+    // remove(start, end)
+    //    Take `[start, end)` out of the regions, splitting the ones that
+    //    only partly overlap it.
+    pub fn remove(&mut self, start: usize, end: usize) {
+        let mut kept = Vec::with_capacity(self.vmas.len() + 1);
+        for v in self.vmas.iter() {
+            if v.end <= start || end <= v.start {
+                kept.push(*v);
+                continue;
+            }
+            if v.start < start {
+                kept.push(Vma { start: v.start, end: start, prot: v.prot });
+            }
+            if end < v.end {
+                kept.push(Vma { start: end, end: v.end, prot: v.prot });
+            }
+        }
+        self.vmas = kept;
+    }
+
+    // protect(start, end, prot)
+    //    Set the protection of `[start, end)`, which must be covered by
+    //    regions, to `prot`.
+    pub fn protect(&mut self, start: usize, end: usize, prot: i32) {
+        self.remove(start, end);
+        self.insert(start, end, prot);
+    }
+
+    // clear()
+    //    Forget every region.
+    pub fn clear(&mut self) {
+        self.vmas.clear();
+    }
+
+    // clone_from(other)
+    //    Replace the regions with a copy of `other`'s.
+    pub fn clone_from(&mut self, other: &VmaList) {
+        self.vmas.clone_from(&other.vmas);
+    }
+
+    // merge()
+    //    Join neighboring regions with the same protection.
+    fn merge(&mut self) {
+        self.vmas.dedup_by(|next, prev| {
+            if prev.end == next.start && prev.prot == next.prot {
+                prev.end = next.end;
+                true
+            } else {
+                false
+            }
+        });
+    }
+}
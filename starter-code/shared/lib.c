@@ -6,7 +6,7 @@
 //    Functions useful in both kernel and applications.
 
 
-// memcpy, memmove, memset, strcmp, strlen, strnlen
+// memcpy, memmove, memset, memcmp, strcmp, strlen, strnlen
 //    We must provide our own implementations.
 
 void* memcpy(void* dst, const void* src, size_t n) {
@@ -40,6 +40,17 @@ void* memset(void* v, int c, size_t n) {
     return v;
 }
 
+int memcmp(const void* a, const void* b, size_t n) {
+    const unsigned char* sa = (const unsigned char*) a;
+    const unsigned char* sb = (const unsigned char*) b;
+    for (; n > 0; ++sa, ++sb, --n) {
+        if (*sa != *sb) {
+            return (*sa > *sb) - (*sa < *sb);
+        }
+    }
+    return 0;
+}
+
 size_t strlen(const char* s) {
     size_t n;
     for (n = 0; *s != '\0'; ++s) {
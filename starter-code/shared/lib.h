@@ -39,6 +39,7 @@ typedef int pid_t;                    // process IDs
 void* memcpy(void* dst, const void* src, size_t n);
 void* memmove(void* dst, const void* src, size_t n);
 void* memset(void* s, int c, size_t n);
+int memcmp(const void* a, const void* b, size_t n);
 size_t strlen(const char* s);
 size_t strnlen(const char* s, size_t maxlen);
 char* strcpy(char* dst, const char* src);
@@ -106,6 +107,37 @@ void panic(const char* format, ...) __attribute__((noinline, noreturn));
 #define INT_SYS_MEM_TOG         (INT_SYS + 8)
 #define INT_SYS_BRK             (INT_SYS + 9)
 #define INT_SYS_SBRK            (INT_SYS + 10)
+#define INT_SYS_MMAP            (INT_SYS + 11)
+#define INT_SYS_MUNMAP          (INT_SYS + 12)
+#define INT_SYS_MPROTECT        (INT_SYS + 13)
+#define INT_SYS_SHM_CREATE      (INT_SYS + 14)
+#define INT_SYS_SHM_ATTACH      (INT_SYS + 15)
+#define INT_SYS_SHM_DETACH      (INT_SYS + 16)
+#define INT_SYS_EXEC            (INT_SYS + 17)
+#define INT_SYS_GETPRIORITY     (INT_SYS + 18)
+#define INT_SYS_SETPRIORITY     (INT_SYS + 19)
+#define INT_SYS_TICKS           (INT_SYS + 20)
+#define INT_SYS_SET_TICKETS     (INT_SYS + 21)
+#define INT_SYS_CPU_SHARE       (INT_SYS + 22)
+#define INT_SYS_SLEEP           (INT_SYS + 23)
+
+// Memory protection for sys_mmap and sys_mprotect
+
+#define PROT_NONE               0       // no access
+#define PROT_READ               1       // readable
+#define PROT_WRITE              2       // writable (needs PROT_READ)
+#define MAP_FAILED              ((void*) -1)
+
+// Scheduling priorities for sys_getpriority and sys_setpriority
+
+#define PRIO_MIN                0       // least favored
+#define PRIO_DEFAULT            20      // priority of new processes
+#define PRIO_MAX                39      // most favored
+
+// Tickets for sys_set_tickets
+
+#define TICKETS_DEFAULT         100     // tickets of new processes
+#define TICKETS_MAX             1000
 
 // Console printing
 
@@ -39,7 +39,7 @@ static inline int pageindex(uintptr_t addr, int level) {
 #define PAGEOFFSET(addr)       ((uintptr_t) (addr) & PAGEOFFMASK)
 
 // The physical address contained in a page table entry
-#define PTE_ADDR(pageentry)     ((uintptr_t) (pageentry) & ~0xFFFUL)
+#define PTE_ADDR(pageentry)     ((uintptr_t) (pageentry) & 0x000FFFFFFFFFF000UL)
 
 // Page table entry flags
 #define PTE_FLAGS(pageentry)    ((x86_64_pageentry_t) (pageentry) & 0xFFFU)
@@ -51,6 +51,8 @@ static inline int pageindex(uintptr_t addr, int level) {
 #define PTE_A   ((x86_64_pageentry_t) 32)   // entry was Accessed (read/written)
 #define PTE_D   ((x86_64_pageentry_t) 64)   // entry was Dirtied (written)
 #define PTE_PS  ((x86_64_pageentry_t) 128)  // entry has a large Page Size
+#define PTE_NX  ((x86_64_pageentry_t) 1 << 63)  // entry is Not eXecutable
+                                            //   (needs IA32_EFER_NXE)
 // - There are other flags too!
 
 // Page fault error flags
@@ -61,6 +63,8 @@ static inline int pageindex(uintptr_t addr, int level) {
 #define PFERR_WRITE     0x2             // Fault happened on a write
 #define PFERR_USER      0x4             // Fault happened in an application
                                         //   (user mode) (rather than kernel)
+#define PFERR_INSTRUCTION 0x10          // Fault happened on an instruction
+                                        //   fetch
 
 
 // struct x86_64_registers
@@ -0,0 +1,53 @@
+#include "process.h"
+#include "lib.h"
+
+// program that checks the kernel passes an argument vector: the boot
+// command runs it with no arguments, and it execs itself with some. The
+// new image reports back through the report page (see process.h).
+
+void process_main(int argc, char* argv[]) {
+    assert(argc >= 1);
+    assert(strcmp(argv[0], "test") == 0);
+    assert(argv[argc] == NULL);
+    assert((uintptr_t) argv % 16 == 0);
+
+    report* r = report_find();
+    if (r) {
+        // the exec'd child
+        assert(r->parent != sys_getpid());
+        assert(argc == 4);
+        assert(strcmp(argv[1], "one") == 0);
+        assert(strcmp(argv[2], "") == 0);
+        assert(strcmp(argv[3], "three") == 0);
+        r->value[0] = argc;
+        sys_exit();
+    }
+    assert(argc == 1);
+
+    r = report_open();
+
+    // too many arguments, or unreadable ones, are refused
+    char* many[18];
+    for (int i = 0; i < 17; ++i) {
+        many[i] = "x";
+    }
+    many[17] = NULL;
+    assert(sys_exec("test", many) == -1);
+    char* bad[] = {"test", (char*) 0x40000, NULL};      // kernel memory
+    assert(sys_exec("test", bad) == -1);
+    assert(sys_exec("test", (char**) 0x40000) == -1);
+
+    pid_t child = sys_fork();
+    assert(child >= 0);
+    if (child == 0) {
+        char* args[] = {"test", "one", "", "three", NULL};
+        sys_exec("test", args);
+        panic("Error, sys_exec returned!");
+    }
+    while (r->value[0] == 0) {
+        sys_yield();
+    }
+    assert(r->value[0] == 4);
+
+    TEST_PASS();
+}
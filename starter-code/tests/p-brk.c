@@ -0,0 +1,45 @@
+#include "process.h"
+#include "lib.h"
+
+extern uint8_t end[];
+
+// program that checks sys_brk and sys_sbrk grow and shrink the heap,
+// and refuse to move the break outside of [end, stack)
+
+void process_main(void) {
+    pid_t p = sys_getpid();
+    srand(p);
+
+    // the program break starts right after the data segment
+    uint8_t* heap_start = sys_sbrk(0);
+    assert(heap_start == end);
+
+    // grow the heap by a bit more than three pages and use all of it
+    uint8_t* old_brk = sys_sbrk(3 * PAGESIZE + 16);
+    assert(old_brk == heap_start);
+    uint8_t* brk = sys_sbrk(0);
+    assert(brk == heap_start + 3 * PAGESIZE + 16);
+    for (uint8_t* addr = heap_start; addr < brk; ++addr) {
+        assert(*addr == 0 && "Error: heap memory was not zeroed!");
+        *addr = p;
+    }
+
+    // shrinking the break unmaps the pages above it
+    assert(sys_brk(heap_start) == 0);
+    assert(sys_sbrk(0) == heap_start);
+    vamapping map;
+    sys_mapping((uintptr_t) ROUNDUP(heap_start, PAGESIZE), &map);
+    if (map.pn != -1)
+        panic("Error, heap page still mapped after shrinking the break!");
+
+    // the break cannot drop below the data segment or reach into the stack
+    if (sys_brk(heap_start - 1) != -1)
+        panic("Error, brk allowed below the data segment!");
+    if (sys_sbrk(-PAGESIZE) != (void*) -1)
+        panic("Error, sbrk allowed below the data segment!");
+    uint8_t* stack_bottom = ROUNDDOWN((uint8_t*) read_rsp() - 1, PAGESIZE);
+    if (sys_brk(stack_bottom + 1) != -1)
+        panic("Error, brk allowed into the stack!");
+
+    TEST_PASS();
+}
@@ -0,0 +1,44 @@
+#include "process.h"
+#include "lib.h"
+
+// program that checks a failed sys_exec leaves the caller running its old
+// image, and that a successful one starts the program afresh in the same
+// process. The child execs this very program ("test"), and the new image
+// reports back through the report page (see process.h).
+
+int marker;
+
+void process_main(void) {
+    report* r = report_find();
+    if (r) {
+        // the exec'd child: globals start over
+        assert(marker == 0);
+        assert(r->parent != sys_getpid());
+        r->value[0] = sys_getpid();
+        sys_exit();
+    }
+
+    r = report_open();
+    marker = 1;
+
+    // failures keep the current image
+    assert(sys_exec("no-such-program", NULL) == -1);
+    assert(sys_exec((const char*) 0x40000, NULL) == -1);     // kernel memory
+    assert(sys_exec(NULL, NULL) == -1);
+    assert(marker == 1);
+
+    pid_t child = sys_fork();
+    assert(child >= 0);
+    if (child == 0) {
+        marker = 2;
+        sys_exec("test", NULL);
+        panic("Error, sys_exec returned!");
+    }
+    while (r->value[0] == 0) {
+        sys_yield();
+    }
+    assert(r->value[0] == child);
+    assert(marker == 1);
+
+    TEST_PASS();
+}
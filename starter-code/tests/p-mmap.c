@@ -0,0 +1,60 @@
+#include "process.h"
+#include "lib.h"
+
+extern uint8_t end[];
+
+// program that checks sys_mmap hands out lazily zero-filled memory between
+// the heap and the stack, and that sys_mprotect and sys_munmap work on it
+
+void process_main(void) {
+    pid_t p = sys_getpid();
+    srand(p);
+
+    // a read-write region lies between the break and the stack
+    uint8_t* region = sys_mmap(3 * PAGESIZE, PROT_READ | PROT_WRITE);
+    assert(region != MAP_FAILED);
+    assert((uintptr_t) region % PAGESIZE == 0);
+    assert(region >= (uint8_t*) sys_sbrk(0));
+    assert(region + 3 * PAGESIZE <= (uint8_t*) read_rsp());
+
+    // nothing is mapped before the first access
+    assert(mapped_perm(region) == -1);
+    for (uint8_t* addr = region; addr < region + 3 * PAGESIZE; ++addr) {
+        assert(*addr == 0 && "Error: mmap memory was not zeroed!");
+        *addr = p;
+    }
+    assert(mapped_perm(region + PAGESIZE) == (PTE_P | PTE_W | PTE_U));
+
+    // the heap cannot grow into the region
+    if (sys_brk(region + 1) != -1)
+        panic("Error, brk allowed into an mmap region!");
+
+    // read-only keeps the contents
+    assert(sys_mprotect(region, PAGESIZE, PROT_READ) == 0);
+    assert(mapped_perm(region) == (PTE_P | PTE_U));
+    assert(region[PAGESIZE - 1] == p);
+    // only mmap'd memory can be protected
+    assert(sys_mprotect(ROUNDDOWN(end - 1, PAGESIZE), PAGESIZE, PROT_READ) == -1);
+    assert(sys_mprotect(region, PAGESIZE, PROT_WRITE) == -1);
+
+    // a no-access guard region is never filled in
+    uint8_t* guard = sys_mmap(PAGESIZE, PROT_NONE);
+    assert(guard != MAP_FAILED && guard != region);
+    assert(guard + PAGESIZE <= region || guard >= region + 3 * PAGESIZE);
+    assert(mapped_perm(guard) == -1);
+
+    // munmap releases the pages; the range can be handed out again
+    assert(sys_munmap(region, 3 * PAGESIZE) == 0);
+    assert(mapped_perm(region) == -1);
+    assert(mapped_perm(region + 2 * PAGESIZE) == -1);
+    assert(sys_mprotect(region, PAGESIZE, PROT_READ) == -1);
+    uint8_t* again = sys_mmap(PAGESIZE, PROT_READ | PROT_WRITE);
+    assert(again != MAP_FAILED);
+    assert(*again == 0);
+
+    assert(sys_mmap(0, PROT_READ) == MAP_FAILED);
+    assert(sys_mmap(PAGESIZE, 4) == MAP_FAILED);
+    assert(sys_munmap(region + 1, PAGESIZE) == -1);
+
+    TEST_PASS();
+}
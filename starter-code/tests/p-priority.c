@@ -0,0 +1,46 @@
+#include "process.h"
+#include "lib.h"
+
+// program that checks sys_getpriority and sys_setpriority: a process can
+// change its own priority and its children's, but not its parent's, and
+// children inherit the priority on fork
+
+void process_main(void) {
+    pid_t self = sys_getpid();
+    assert(sys_getpriority(0) == PRIO_DEFAULT);
+    assert(sys_getpriority(self) == PRIO_DEFAULT);
+
+    assert(sys_setpriority(0, PRIO_MAX) == 0);
+    assert(sys_getpriority(self) == PRIO_MAX);
+    assert(sys_setpriority(self, PRIO_MIN) == 0);
+    assert(sys_getpriority(0) == PRIO_MIN);
+
+    // out of range priorities and unknown processes are refused
+    assert(sys_setpriority(0, PRIO_MAX + 1) == -1);
+    assert(sys_setpriority(0, PRIO_MIN - 1) == -1);
+    assert(sys_getpriority(0) == PRIO_MIN);
+    assert(sys_getpriority(-1) == -1);
+    assert(sys_getpriority(16) == -1);         // past the last slot
+    assert(sys_setpriority(15, PRIO_DEFAULT) == -1);   // a free slot
+
+    assert(sys_setpriority(0, 30) == 0);
+    pid_t child = sys_fork();
+    assert(child >= 0);
+    if (child == 0) {
+        assert(sys_getpriority(0) == 30);
+        // the parent is not ours to change
+        assert(sys_getpriority(self) == -1);
+        assert(sys_setpriority(self, PRIO_MAX) == -1);
+        while (sys_getpriority(0) != 10) {
+            sys_yield();
+        }
+        sys_exit();
+    }
+
+    assert(sys_getpriority(child) == 30);
+    assert(sys_setpriority(child, 10) == 0);
+    assert(sys_getpriority(child) == 10);
+    assert(sys_getpriority(0) == 30);
+
+    TEST_PASS();
+}
@@ -0,0 +1,53 @@
+#include "process.h"
+#include "lib.h"
+
+// program that checks a shared memory segment is zeroed, stays shared
+// (not copy-on-write) across fork, and can be attached and detached
+
+void process_main(void) {
+    int id = sys_shm_create(2 * PAGESIZE);
+    assert(id >= 0);
+
+    // the kernel picks an address between the break and the stack
+    volatile uint8_t* seg = sys_shm_attach(id, NULL);
+    assert(seg != (void*) -1);
+    assert((uintptr_t) seg % PAGESIZE == 0);
+    assert(mapped_perm((void*) seg) == (PTE_P | PTE_W | PTE_U));
+    for (size_t i = 0; i < 2 * PAGESIZE; ++i) {
+        assert(seg[i] == 0 && "Error: shared memory was not zeroed!");
+    }
+
+    // a second attachment at a chosen address sees the same memory
+    volatile uint8_t* alias = sys_shm_attach(id, (void*) (seg - 4 * PAGESIZE));
+    assert(alias == seg - 4 * PAGESIZE);
+    seg[PAGESIZE + 1] = 17;
+    assert(alias[PAGESIZE + 1] == 17);
+    assert(sys_shm_detach((void*) alias) == 0);
+    assert(mapped_perm((void*) alias) == -1);
+
+    // the child's writes are visible to the parent
+    pid_t child = sys_fork();
+    assert(child >= 0);
+    if (child == 0) {
+        assert(seg[PAGESIZE + 1] == 17);
+        seg[0] = 42;
+        seg[PAGESIZE] = 43;
+        sys_exit();
+    }
+    while (seg[0] != 42) {
+        sys_yield();
+    }
+    assert(seg[PAGESIZE] == 43);
+
+    // overlapping or unaligned attachments and bad ids are refused
+    assert(sys_shm_attach(id, (void*) seg) == (void*) -1);
+    assert(sys_shm_attach(id, (void*) (seg + 1)) == (void*) -1);
+    assert(sys_shm_attach(-1, NULL) == (void*) -1);
+    assert(sys_shm_create(0) == -1);
+
+    assert(sys_shm_detach((void*) seg) == 0);
+    assert(mapped_perm((void*) seg) == -1);
+    assert(sys_shm_detach((void*) seg) == -1);
+
+    TEST_PASS();
+}
@@ -0,0 +1,45 @@
+#include "process.h"
+#include "lib.h"
+
+// program that checks sys_sleep: a sleeping process wakes up after its
+// ticks have passed, does not use the CPU meanwhile, and the kernel can
+// wait for a timer tick when every process is asleep
+
+void process_main(void) {
+    assert(sys_sleep(0) == 0);
+    assert(sys_sleep(0xFFFFFFFFU) == -1);
+
+    // the child reports its progress in r->value[0], and in r->value[1]
+    // how many ticks it has run by the time it wakes up
+    report* r = report_open();
+
+    pid_t child = sys_fork();
+    assert(child >= 0);
+    if (child == 0) {
+        r->value[0] = 1;
+        assert(sys_sleep(50) == 0);
+        r->value[1] = sys_ticks(0);
+        r->value[0] = 2;
+        sys_exit();
+    }
+
+    while (r->value[0] != 1) {
+        sys_yield();
+    }
+    // while the child sleeps, every tick is charged to this process
+    int start = sys_ticks(0);
+    while (r->value[0] != 2) {
+    }
+    assert(sys_ticks(0) - start >= 49);
+    assert(r->value[1] <= 5);
+
+    // with nobody else to run, the kernel waits for the ticks to pass
+    while (sys_ticks(child) != -1) {
+        sys_yield();
+    }
+    start = sys_ticks(0);
+    assert(sys_sleep(10) == 0);
+    assert(sys_ticks(0) - start <= 1);
+
+    TEST_PASS();
+}
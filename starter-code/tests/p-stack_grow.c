@@ -0,0 +1,39 @@
+#include "process.h"
+#include "lib.h"
+
+#define DEPTH 40
+extern uint8_t end[];
+
+// program that checks the stack grows on demand past its first page
+
+// each call uses a bit more than a quarter page of stack
+static int recurse(int depth) {
+    volatile uint8_t buf[1024];
+    buf[0] = depth;
+    buf[sizeof(buf) - 1] = depth;
+    if (depth == 0) {
+        return 0;
+    }
+    int sum = recurse(depth - 1);
+    assert(buf[0] == depth && buf[sizeof(buf) - 1] == depth);
+    return sum + 1;
+}
+
+void process_main(void) {
+    pid_t p = sys_getpid();
+    srand(p);
+
+    uint8_t* stack_top = ROUNDUP((uint8_t*) read_rsp(), PAGESIZE);
+
+    // the first call writes to the low end of its frame, several pages
+    // below the initial stack page
+    assert(recurse(DEPTH) == DEPTH);
+
+    // the stack pages touched by the recursion are now mapped and writable
+    vamapping smap;
+    sys_mapping((uintptr_t) (stack_top - 8 * PAGESIZE), &smap);
+    if ((smap.perm & (PTE_P | PTE_W | PTE_U)) != (PTE_P | PTE_W | PTE_U))
+        panic("Error, stack did not grow on demand!");
+
+    TEST_PASS();
+}
@@ -0,0 +1,44 @@
+#include "process.h"
+#include "lib.h"
+
+// program that checks the stride scheduler splits the CPU in proportion
+// to tickets: two busy children with 100 and 300 tickets should get
+// about a quarter and three quarters of it.
+// Run it with the stride scheduler (press 's' instead of 't').
+
+void process_main(void) {
+    assert(sys_set_tickets(0) == -1);
+    assert(sys_set_tickets(TICKETS_MAX + 1) == -1);
+    // the parent mostly waits
+    assert(sys_set_tickets(1) == 0);
+
+    // r->value[0] tells the children to stop
+    report* r = report_open();
+
+    pid_t children[2];
+    unsigned tickets[2] = {100, 300};
+    for (int i = 0; i < 2; ++i) {
+        children[i] = sys_fork();
+        assert(children[i] >= 0);
+        if (children[i] == 0) {
+            assert(sys_set_tickets(tickets[i]) == 0);
+            while (!r->value[0]) {
+            }
+            sys_exit();
+        }
+    }
+
+    while (sys_ticks(children[0]) + sys_ticks(children[1]) < 400) {
+        sys_yield();
+    }
+    int low = sys_cpu_share(children[0]);
+    int high = sys_cpu_share(children[1]);
+    r->value[0] = 1;
+    app_printf(0, "shares: %d/1000 with 100 tickets, %d/1000 with 300\n",
+               low, high);
+    assert(low > 0);
+    assert(high >= 2 * low && high <= 4 * low);
+    assert(sys_cpu_share(0) < low);
+
+    TEST_PASS();
+}
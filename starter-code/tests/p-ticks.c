@@ -0,0 +1,37 @@
+#include "process.h"
+#include "lib.h"
+
+// program that checks timer ticks are charged to the process that was
+// running: a busy child accumulates ticks while its parent only counts
+// the ones that hit it
+
+void process_main(void) {
+    pid_t self = sys_getpid();
+    assert(sys_ticks(0) >= 0);
+    assert(sys_ticks(self) >= 0);
+    assert(sys_ticks(-1) == -1);
+    assert(sys_ticks(16) == -1);        // past the last slot
+    assert(sys_ticks(15) == -1);        // a free slot
+
+    // busy-wait until the timer has hit this process a few times
+    int start = sys_ticks(0);
+    while (sys_ticks(0) < start + 3) {
+    }
+
+    pid_t child = sys_fork();
+    assert(child >= 0);
+    if (child == 0) {
+        // forked processes start from scratch
+        assert(sys_ticks(0) <= 1);
+        while (sys_ticks(0) < 5) {
+        }
+        sys_exit();
+    }
+
+    while (sys_ticks(child) != -1 && sys_ticks(child) < 5) {
+        sys_yield();
+    }
+    assert(sys_ticks(0) >= start + 3);
+
+    TEST_PASS();
+}
@@ -0,0 +1,35 @@
+#include "process.h"
+#include "lib.h"
+
+extern uint8_t etext[];
+extern uint8_t end[];
+
+uint8_t data_byte = 1;
+
+// program that checks ELF segments are mapped with their own permissions:
+// text is read-only, data and bss are writable, also after fork
+
+void process_main(void) {
+    // code and read-only data
+    assert(mapped_perm((void*) process_main) == (PTE_P | PTE_U));
+    assert(mapped_perm(etext - 1) == (PTE_P | PTE_U));
+    // data and bss
+    assert(mapped_perm(&data_byte) == (PTE_P | PTE_W | PTE_U));
+    assert(mapped_perm(end - 1) == (PTE_P | PTE_W | PTE_U));
+
+    pid_t child = sys_fork();
+    assert(child >= 0);
+
+    // text stays read-only in both processes, and data is still private
+    // and writable after fork
+    assert(mapped_perm((void*) process_main) == (PTE_P | PTE_U));
+    data_byte = child == 0 ? 2 : 3;
+    assert(mapped_perm(&data_byte) == (PTE_P | PTE_W | PTE_U));
+    sys_yield();
+    assert(data_byte == (child == 0 ? 2 : 3));
+
+    if (child == 0) {
+        sys_exit();
+    }
+    TEST_PASS();
+}
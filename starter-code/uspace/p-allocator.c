@@ -9,12 +9,21 @@ uint8_t* heap_top;
 uint8_t* stack_bottom;
 
 // Program that starts allocating page by page from its heap
-// till it reaches stack OR runs out of memory
+// till it reaches stack OR runs out of memory.
+// An argument sets its scheduling priority.
 
-void process_main(void) {
+void process_main(int argc, char* argv[]) {
     pid_t p = sys_getpid();
     srand(p);
 
+    if (argc > 1) {
+        int priority = 0;
+        for (const char* s = argv[1]; *s >= '0' && *s <= '9'; ++s) {
+            priority = 10 * priority + (*s - '0');
+        }
+        sys_setpriority(0, priority);
+    }
+
     // The heap starts on the page right after the 'end' symbol,
     // whose address is the first address not allocated to process code
     // or data.
@@ -109,9 +109,8 @@ static inline void sys_mem_tog(pid_t p) {
 //     decreasing the break deallocates memory
 //     on success, returns 0
 //     on failure, return -1
-//     brk cannot exceed MEMSIZE_VIRTUAL, and cannot be lower than data segment (loaded
+//     brk cannot reach into the stack, and cannot be lower than data segment (loaded
 //     by the loader)
-//     Not expected to be implemented
 
 static inline int sys_brk(const void* addr) {
     static int result;
@@ -128,7 +127,6 @@ static inline int sys_brk(const void* addr) {
 //     On success, sbrk() returns the previous program break
 //     (If the break was increased, then this value is a pointer to the start of the newly allocated memory)
 //      On error, (void *) -1 is returned
-//     Not expected to be implemented
 static inline void * sys_sbrk(const intptr_t increment) {
     static void * result;
     asm volatile ("int %1" :  "=a" (result)
@@ -137,8 +135,236 @@ static inline void * sys_sbrk(const intptr_t increment) {
     return result;
 }
 
+// sys_mmap(len, prot)
+//     map `len` bytes (rounded up to whole pages) of anonymous memory with
+//     protection `prot`: PROT_NONE, PROT_READ or PROT_READ | PROT_WRITE
+//     the kernel picks the address, between the heap and the stack
+//     pages are zero-filled on first access
+//     On success, returns the start of the new region
+//     On error, MAP_FAILED is returned
+static inline void* sys_mmap(size_t len, int prot) {
+    void* result;
+    asm volatile ("int %1" :  "=a" (result)
+                  : "i" (INT_SYS_MMAP), "D" /* %rdi */ (len),
+                    "S" /* %rsi */ (prot)
+                  : "cc", "memory");
+    return result;
+}
+
+// sys_munmap(addr, len)
+//     remove the mmap'd memory in [addr, addr + len) and free its pages
+//     `addr` must be page-aligned; memory not obtained from sys_mmap is
+//     left alone
+//     on success, returns 0
+//     on failure, return -1
+static inline int sys_munmap(void* addr, size_t len) {
+    int result;
+    asm volatile ("int %1" :  "=a" (result)
+                  : "i" (INT_SYS_MUNMAP), "D" /* %rdi */ (addr),
+                    "S" /* %rsi */ (len)
+                  : "cc", "memory");
+    return result;
+}
+
+// sys_mprotect(addr, len, prot)
+//     change the protection of the mmap'd memory in [addr, addr + len)
+//     to `prot`; the whole range must come from sys_mmap
+//     on success, returns 0
+//     on failure, return -1
+static inline int sys_mprotect(void* addr, size_t len, int prot) {
+    int result;
+    asm volatile ("int %1" :  "=a" (result)
+                  : "i" (INT_SYS_MPROTECT), "D" /* %rdi */ (addr),
+                    "S" /* %rsi */ (len), "d" /* %rdx */ (prot)
+                  : "cc", "memory");
+    return result;
+}
+
+// sys_shm_create(size)
+//     create a shared memory segment of `size` bytes (rounded up to whole
+//     pages), initially zero
+//     on success, returns the segment's id, which other processes can use
+//     to attach it
+//     on failure, return -1
+static inline int sys_shm_create(size_t size) {
+    int result;
+    asm volatile ("int %1" :  "=a" (result)
+                  : "i" (INT_SYS_SHM_CREATE), "D" /* %rdi */ (size)
+                  : "cc", "memory");
+    return result;
+}
+
+// sys_shm_attach(id, addr)
+//     map shared memory segment `id` read-write at the page-aligned address
+//     `addr`, or at an address the kernel picks if `addr` is NULL
+//     the segment stays attached in children after sys_fork
+//     On success, returns the address of the segment
+//     On error, (void *) -1 is returned
+static inline void* sys_shm_attach(int id, void* addr) {
+    void* result;
+    asm volatile ("int %1" :  "=a" (result)
+                  : "i" (INT_SYS_SHM_ATTACH), "D" /* %rdi */ (id),
+                    "S" /* %rsi */ (addr)
+                  : "cc", "memory");
+    return result;
+}
+
+// sys_shm_detach(addr)
+//     unmap the shared memory segment attached at `addr`; the segment is
+//     freed once no process has it attached
+//     on success, returns 0
+//     on failure, return -1
+static inline int sys_shm_detach(void* addr) {
+    int result;
+    asm volatile ("int %1" :  "=a" (result)
+                  : "i" (INT_SYS_SHM_DETACH), "D" /* %rdi */ (addr)
+                  : "cc", "memory");
+    return result;
+}
+
+// sys_getpriority(pid)
+//     return the scheduling priority of process `pid`, which must be the
+//     current process (or 0 for it) or one of its children
+//     on failure, returns -1
+static inline int sys_getpriority(pid_t pid) {
+    int result;
+    asm volatile ("int %1" :  "=a" (result)
+                  : "i" (INT_SYS_GETPRIORITY), "D" /* %rdi */ (pid)
+                  : "cc", "memory");
+    return result;
+}
+
+// sys_setpriority(pid, priority)
+//     set the scheduling priority of process `pid` (see sys_getpriority)
+//     to `priority`, from PRIO_MIN to PRIO_MAX; higher priorities get
+//     more turns when the kernel runs the priority scheduler. New
+//     processes start at PRIO_DEFAULT, and children inherit it on fork.
+//     on success, returns 0
+//     on failure, return -1
+static inline int sys_setpriority(pid_t pid, int priority) {
+    int result;
+    asm volatile ("int %1" :  "=a" (result)
+                  : "i" (INT_SYS_SETPRIORITY), "D" /* %rdi */ (pid),
+                    "S" /* %rsi */ (priority)
+                  : "cc", "memory");
+    return result;
+}
+
+// sys_ticks(pid)
+//     return the number of timer interrupts that arrived while process
+//     `pid` (0 for the current process) was running, since it started or
+//     was forked; exec does not reset it
+//     on failure (no such process), returns -1
+static inline int sys_ticks(pid_t pid) {
+    int result;
+    asm volatile ("int %1" :  "=a" (result)
+                  : "i" (INT_SYS_TICKS), "D" /* %rdi */ (pid)
+                  : "cc", "memory");
+    return result;
+}
+
+// sys_set_tickets(n)
+//     give the current process `n` tickets, from 1 to TICKETS_MAX; with
+//     the stride scheduler, runnable processes get the CPU in proportion
+//     to their tickets. New processes start with TICKETS_DEFAULT, and
+//     children inherit them on fork.
+//     on success, returns 0
+//     on failure, return -1
+static inline int sys_set_tickets(unsigned n) {
+    int result;
+    asm volatile ("int %1" :  "=a" (result)
+                  : "i" (INT_SYS_SET_TICKETS), "D" /* %rdi */ (n)
+                  : "cc", "memory");
+    return result;
+}
+
+// sys_cpu_share(pid)
+//     return the share of the CPU process `pid` (0 for the current
+//     process) got since it started or was forked, in thousandths: the
+//     timer ticks charged to it (see sys_ticks) out of all ticks since
+//     on failure (no such process), returns -1
+static inline int sys_cpu_share(pid_t pid) {
+    int result;
+    asm volatile ("int %1" :  "=a" (result)
+                  : "i" (INT_SYS_CPU_SHARE), "D" /* %rdi */ (pid)
+                  : "cc", "memory");
+    return result;
+}
+
+// sys_sleep(n)
+//     block the current process until `n` more timer ticks have passed;
+//     other processes run in the meantime, and a sleeping process does not
+//     use the CPU. sys_sleep(0) returns right away.
+//     on success, returns 0
+//     on failure (`n` too large), returns -1
+static inline int sys_sleep(unsigned n) {
+    int result;
+    asm volatile ("int %1" :  "=a" (result)
+                  : "i" (INT_SYS_SLEEP), "D" /* %rdi */ (n)
+                  : "cc", "memory");
+    return result;
+}
+
+// sys_exec(name, argv)
+//     replace the current program with the program called `name`
+//     ("allocator", "fork", "test", ...); the process keeps its pid, but
+//     its memory, mmap regions and shared memory attachments are dropped
+//     and the new program starts as `process_main(argc, argv)`
+//     `argv` is a NULL-terminated array of at most 16 strings, which by
+//     convention starts with `name`; NULL passes just `name`
+//     on success, does not return
+//     on failure (unknown program, bad arguments, out of memory), returns
+//     -1 and the current program keeps running
+static inline int sys_exec(const char* name, char* const argv[]) {
+    int result;
+    asm volatile ("int %1" :  "=a" (result)
+                  : "i" (INT_SYS_EXEC), "D" /* %rdi */ (name),
+                    "S" /* %rsi */ (argv)
+                  : "cc", "memory");
+    return result;
+}
+
 // OTHER HELPER FUNCTIONS
 
+// mapped_perm(addr)
+//    Return the PTE_P, PTE_W and PTE_U permissions the current process has
+//    at `addr`, or -1 if it is not mapped.
+static inline int mapped_perm(const void* addr) {
+    vamapping map;
+    sys_mapping((uintptr_t) addr, &map);
+    return map.pn < 0 ? -1 : (int) (map.perm & (PTE_P | PTE_W | PTE_U));
+}
+
+// report
+//    A page a test program shares with the processes it starts, so that a
+//    child can report back to its parent even after sys_exec. It is
+//    shared memory segment 0; `parent` is the pid of the process that
+//    created it.
+typedef struct report {
+    pid_t parent;
+    volatile int value[4];
+} report;
+
+// report_open()
+//    Create the report page and attach it. Must come before the test
+//    creates any other shared memory segment.
+static inline report* report_open(void) {
+    int id = sys_shm_create(PAGESIZE);
+    assert(id == 0);
+    report* r = (report*) sys_shm_attach(id, NULL);
+    assert(r != (void*) -1);
+    r->parent = sys_getpid();
+    return r;
+}
+
+// report_find()
+//    Attach the report page an earlier process created. Returns NULL if
+//    there is none.
+static inline report* report_find(void) {
+    report* r = (report*) sys_shm_attach(0, NULL);
+    return r == (void*) -1 ? NULL : r;
+}
+
 // app_printf(format, ...)
 //    Calls console_printf() (see lib.h). The cursor position is read from
 //    `cursorpos`, a shared variable defined by the kernel, and written back